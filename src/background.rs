@@ -0,0 +1,157 @@
+use std::f64::consts::PI;
+use crate::utils::{Point3, Color3, BLACK, UNIT_Z};
+use crate::error::RaytraceError;
+
+/// What rays that hit nothing see, by their unit direction.
+pub trait Background: Send + Sync {
+    fn radiance(&self, direction: Point3) -> Color3;
+    /// Whether it is sampled as a light, by `sample` and `pdf`.
+    fn is_emitter(&self) -> bool {
+        false
+    }
+    /// A unit direction into the background, with `u` uniform in [0, 1)^2.
+    fn sample(&self, _u: (f64, f64)) -> Option<Point3> {
+        None
+    }
+    /// Solid angle density of [`Background::sample`] giving a unit direction.
+    fn pdf(&self, _direction: Point3) -> f64 {
+        0.0
+    }
+}
+
+/// The same color in every direction.
+pub struct Solid {
+    pub color: Color3
+}
+
+impl Solid {
+    pub fn construct(color: Color3) -> Result<Solid, RaytraceError> {
+        Ok(Solid {color: Color3::construct(color.r, color.g, color.b)?})
+    }
+}
+
+impl Background for Solid {
+    fn radiance(&self, _direction: Point3) -> Color3 {
+        self.color
+    }
+}
+
+/// No light at all, for scenes lit only by their own lights.
+pub struct Black;
+
+impl Background for Black {
+    fn radiance(&self, _direction: Point3) -> Color3 {
+        BLACK
+    }
+}
+
+/// Blend from the nadir color straight down to the zenith color straight up.
+pub struct Gradient {
+    pub nadir: Color3,
+    pub zenith: Color3,
+    pub up: Point3  // unit
+}
+
+impl Gradient {
+    /// White at the bottom to blue at the top, the sky when a scene gives no background.
+    pub const SKY: Gradient = Gradient {
+        nadir: Color3 {r: 1.0, g: 1.0, b: 1.0},
+        zenith: Color3 {r: 0.4, g: 0.6, b: 1.0},
+        up: UNIT_Z
+    };
+    pub fn construct(nadir: Color3, zenith: Color3, up: Point3) -> Result<Gradient, RaytraceError> {
+        let up = up.unit_vector();
+        if !(up.x.is_finite() && up.y.is_finite() && up.z.is_finite()) {
+            return Err(RaytraceError::ZeroDirection);
+        }
+        Ok(Gradient {
+            nadir: Color3::construct(nadir.r, nadir.g, nadir.b)?,
+            zenith: Color3::construct(zenith.r, zenith.g, zenith.b)?,
+            up
+        })
+    }
+}
+
+impl Background for Gradient {
+    fn radiance(&self, direction: Point3) -> Color3 {
+        let t = 0.5*(direction.dot(self.up) + 1.0);
+        self.nadir*(1.0 - t) + self.zenith*t
+    }
+}
+
+/// Checkerboard of squares `size` degrees of longitude and latitude across (about +Z), to see
+/// which way rays leave the scene while debugging.
+pub struct Checker {
+    pub even: Color3,
+    pub odd: Color3,
+    pub size: f64  // degrees
+}
+
+impl Checker {
+    pub fn construct(even: Color3, odd: Color3, size: f64) -> Result<Checker, RaytraceError> {
+        if !(size > 0.0 && size <= 180.0) {
+            return Err(RaytraceError::InvalidCheckerSize(size));
+        }
+        Ok(Checker {
+            even: Color3::construct(even.r, even.g, even.b)?,
+            odd: Color3::construct(odd.r, odd.g, odd.b)?,
+            size
+        })
+    }
+}
+
+impl Background for Checker {
+    fn radiance(&self, direction: Point3) -> Color3 {
+        let longitude = direction.x.atan2(direction.y) * 180.0 / PI;
+        let latitude = direction.z.clamp(-1.0, 1.0).asin() * 180.0 / PI;
+        let square = (longitude / self.size).floor() + (latitude / self.size).floor();
+        if square.rem_euclid(2.0) == 0.0 { self.even } else { self.odd }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb(color: Color3) -> (f64, f64, f64) {
+        (color.r, color.g, color.b)
+    }
+
+    #[test]
+    fn gradients_blend_along_their_up_axis() {
+        let sideways = Gradient::construct(Color3 {r: 1.0, g: 0.0, b: 0.0}, Color3 {r: 0.0, g: 0.0, b: 1.0}, Point3 {x: 2.0, y: 0.0, z: 0.0}).unwrap();
+        assert_eq!(rgb(sideways.radiance(Point3 {x: 1.0, y: 0.0, z: 0.0})), (0.0, 0.0, 1.0));
+        assert_eq!(rgb(sideways.radiance(Point3 {x: -1.0, y: 0.0, z: 0.0})), (1.0, 0.0, 0.0));
+        assert_eq!(rgb(sideways.radiance(UNIT_Z)), (0.5, 0.0, 0.5));
+        assert_eq!(rgb(Gradient::SKY.radiance(UNIT_Z)), (0.4, 0.6, 1.0));
+        assert!(matches!(Gradient::construct(BLACK, BLACK, Point3 {x: 0.0, y: 0.0, z: 0.0}), Err(RaytraceError::ZeroDirection)));
+    }
+
+    #[test]
+    fn checkers_alternate_in_longitude_and_latitude() {
+        let (even, odd) = (Color3 {r: 1.0, g: 1.0, b: 1.0}, Color3 {r: 0.0, g: 0.5, b: 0.0});
+        let checker = Checker::construct(even, odd, 90.0).unwrap();
+        // longitude from +Y towards +X, latitude up towards +Z
+        let at = |longitude: f64, latitude: f64| {
+            let (longitude, latitude) = (longitude.to_radians(), latitude.to_radians());
+            rgb(checker.radiance(Point3 {x: latitude.cos()*longitude.sin(), y: latitude.cos()*longitude.cos(), z: latitude.sin()}))
+        };
+        assert_eq!(at(45.0, 45.0), rgb(even));
+        assert_eq!(at(135.0, 45.0), rgb(odd));
+        assert_eq!(at(45.0, -45.0), rgb(odd));
+        assert_eq!(at(135.0, -45.0), rgb(even));
+        assert_eq!(at(-45.0, 45.0), rgb(odd));
+        assert!(matches!(Checker::construct(even, odd, 0.0), Err(RaytraceError::InvalidCheckerSize(_))));
+    }
+
+    #[test]
+    fn solid_and_black_are_the_same_everywhere() {
+        let solid = Solid::construct(Color3 {r: 0.2, g: 0.3, b: 0.4}).unwrap();
+        for direction in [UNIT_Z, -UNIT_Z, Point3 {x: 0.6, y: -0.8, z: 0.0}] {
+            assert_eq!(rgb(solid.radiance(direction)), (0.2, 0.3, 0.4));
+            assert_eq!(rgb(Black.radiance(direction)), (0.0, 0.0, 0.0));
+        }
+        assert!(!solid.is_emitter() && solid.sample((0.5, 0.5)).is_none() && solid.pdf(UNIT_Z) == 0.0);
+        assert!(Solid::construct(Color3 {r: -1.0, g: 0.0, b: 0.0}).is_err());
+    }
+}
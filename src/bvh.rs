@@ -0,0 +1,227 @@
+use crate::utils::{Point3, Ray};
+
+/// Axis-aligned bounding box.
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb {
+    pub min: Point3,
+    pub max: Point3
+}
+
+impl Aabb {
+    pub const EMPTY: Aabb = Aabb {
+        min: Point3 {x: f64::INFINITY, y: f64::INFINITY, z: f64::INFINITY},
+        max: Point3 {x: f64::NEG_INFINITY, y: f64::NEG_INFINITY, z: f64::NEG_INFINITY}
+    };
+    pub fn construct(a: Point3, b: Point3) -> Aabb {
+        Aabb::EMPTY.grow(a).grow(b)
+    }
+    pub fn grow(self, pt: Point3) -> Aabb {
+        Aabb {
+            min: Point3 {x: self.min.x.min(pt.x), y: self.min.y.min(pt.y), z: self.min.z.min(pt.z)},
+            max: Point3 {x: self.max.x.max(pt.x), y: self.max.y.max(pt.y), z: self.max.z.max(pt.z)}
+        }
+    }
+    pub fn union(self, other: Aabb) -> Aabb {
+        self.grow(other.min).grow(other.max)
+    }
+    pub fn centroid(self) -> Point3 {
+        (self.min + self.max) / 2.0
+    }
+    pub fn surface_area(self) -> f64 {
+        let d = self.max - self.min;
+        if d.x < 0.0 || d.y < 0.0 || d.z < 0.0 {
+            return 0.0;
+        }
+        2.0 * (d.x*d.y + d.y*d.z + d.z*d.x)
+    }
+    pub fn hit(&self, ray: &Ray, t_range: (f64, f64)) -> bool {
+        // slab test; the exit distance is widened by a few ulps so that rounding
+        // never culls a primitive hit lying exactly on the box (PBRT's gamma(3) bound)
+        const WIDEN: f64 = 1.0 + 2.0 * 3.0 * f64::EPSILON / (1.0 - 3.0 * f64::EPSILON);
+        let (mut t_min, mut t_max) = t_range;
+        for (origin, direction, min, max) in [
+            (ray.B.x, ray.A.x, self.min.x, self.max.x),
+            (ray.B.y, ray.A.y, self.min.y, self.max.y),
+            (ray.B.z, ray.A.z, self.min.z, self.max.z)
+        ] {
+            let inverse = 1.0 / direction;
+            let mut t0 = (min - origin) * inverse;
+            let mut t1 = (max - origin) * inverse;
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // NaN (origin on a slab of a parallel ray) leaves the range untouched
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 * WIDEN < t_max { t1 * WIDEN } else { t_max };
+            if t_min > t_max {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn axis(pt: Point3, axis: usize) -> f64 {
+    match axis {
+        0 => pt.x,
+        1 => pt.y,
+        _ => pt.z
+    }
+}
+
+#[derive(Clone, Debug)]
+struct BvhNode {
+    bounds: Aabb,
+    // leaf: primitives order[start..start + count]; interior (count == 0): children are
+    // the next node and nodes[second], split along axis
+    start: usize,
+    count: usize,
+    second: usize,
+    axis: usize
+}
+
+/// Bounding volume hierarchy over primitives given by their boxes, built with the
+/// surface area heuristic. It only stores primitive indices; the caller intersects them.
+#[derive(Clone, Debug)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    order: Vec<usize>
+}
+
+impl Bvh {
+    const BINS: usize = 16;
+    const MAX_LEAF: usize = 4;
+    const TRAVERSAL_COST: f64 = 1.0;  // relative to one primitive intersection
+
+    pub fn construct(boxes: &[Aabb]) -> Bvh {
+        let mut bvh = Bvh {nodes: Vec::new(), order: (0..boxes.len()).collect()};
+        if !boxes.is_empty() {
+            let centroids: Vec<Point3> = boxes.iter().map(|b| b.centroid()).collect();
+            bvh.build(boxes, &centroids, 0, boxes.len());
+        }
+        bvh
+    }
+    fn build(&mut self, boxes: &[Aabb], centroids: &[Point3], start: usize, end: usize) -> usize {
+        let primitives = &mut self.order[start..end];
+        let bounds = primitives.iter().fold(Aabb::EMPTY, |acc, &i| acc.union(boxes[i]));
+        let centroid_bounds = primitives.iter().fold(Aabb::EMPTY, |acc, &i| acc.grow(centroids[i]));
+        let index = self.nodes.len();
+        self.nodes.push(BvhNode {bounds, start, count: end - start, second: 0, axis: 0});
+
+        let count = end - start;
+        if count == 1 {
+            return index;
+        }
+
+        // binned SAH: pick the axis and bin boundary with the lowest expected cost
+        let mut best: Option<(f64, usize, usize)> = None;  // (cost, axis, bins left of the split)
+        for a in 0..3 {
+            let low = axis(centroid_bounds.min, a);
+            let extent = axis(centroid_bounds.max, a) - low;
+            if extent <= 0.0 {
+                continue;
+            }
+            let bin_of = |c: Point3| (((axis(c, a) - low) / extent * Self::BINS as f64) as usize).min(Self::BINS - 1);
+            let mut bins = [(Aabb::EMPTY, 0usize); Self::BINS];
+            for &i in primitives.iter() {
+                let bin = &mut bins[bin_of(centroids[i])];
+                bin.0 = bin.0.union(boxes[i]);
+                bin.1 += 1;
+            }
+            let mut right_area = [0.0; Self::BINS];
+            let mut right_count = [0usize; Self::BINS];
+            let mut acc = (Aabb::EMPTY, 0);
+            for b in (1..Self::BINS).rev() {
+                acc = (acc.0.union(bins[b].0), acc.1 + bins[b].1);
+                right_area[b] = acc.0.surface_area();
+                right_count[b] = acc.1;
+            }
+            let mut left = (Aabb::EMPTY, 0);
+            for split in 1..Self::BINS {
+                left = (left.0.union(bins[split - 1].0), left.1 + bins[split - 1].1);
+                if left.1 == 0 || right_count[split] == 0 {
+                    continue;
+                }
+                let cost = left.0.surface_area() * left.1 as f64 + right_area[split] * right_count[split] as f64;
+                if best.is_none_or(|(c, ..)| cost < c) {
+                    best = Some((cost, a, split));
+                }
+            }
+        }
+
+        let area = bounds.surface_area();
+        let leaf_cost = count as f64;
+        let (split_axis, mid) = match best {
+            Some((cost, a, split)) => {
+                let cost = Self::TRAVERSAL_COST + if area > 0.0 { cost / area } else { count as f64 };
+                if cost >= leaf_cost && count <= Self::MAX_LEAF {
+                    return index;
+                }
+                let low = axis(centroid_bounds.min, a);
+                let extent = axis(centroid_bounds.max, a) - low;
+                let bin_of = |c: Point3| (((axis(c, a) - low) / extent * Self::BINS as f64) as usize).min(Self::BINS - 1);
+                let mut mid = 0;
+                for k in 0..count {
+                    if bin_of(centroids[primitives[k]]) < split {
+                        primitives.swap(k, mid);
+                        mid += 1;
+                    }
+                }
+                (a, start + mid)
+            },
+            None => {
+                // all centroids coincide: no split helps, but big leaves still get halved
+                if count <= Self::MAX_LEAF {
+                    return index;
+                }
+                (0, start + count / 2)
+            }
+        };
+
+        self.nodes[index].count = 0;
+        self.nodes[index].axis = split_axis;
+        self.build(boxes, centroids, start, mid);
+        let second = self.build(boxes, centroids, mid, end);
+        self.nodes[index].second = second;
+        index
+    }
+    pub fn bounds(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::EMPTY, |node| node.bounds)
+    }
+    /// Closest primitive hit: `hit(i, t_range)` intersects primitive i and returns its t
+    /// with any extra data. Equal t is resolved towards the lower index, so the result is
+    /// exactly what a linear scan keeping the first strictly closer hit would give.
+    pub fn traverse<T>(&self, ray: &Ray, t_range: (f64, f64), mut hit: impl FnMut(usize, (f64, f64)) -> Option<(f64, T)>) -> Option<(usize, f64, T)> {
+        let mut closest: Option<(usize, f64, T)> = None;
+        let mut stack: Vec<usize> = Vec::with_capacity(64);
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        let negative = [ray.A.x < 0.0, ray.A.y < 0.0, ray.A.z < 0.0];
+
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            // next_up keeps hits tied with the current closest, for the index tie break
+            let max_t = closest.as_ref().map_or(t_range.1, |c| c.1.next_up());
+            if !node.bounds.hit(ray, (t_range.0, max_t)) {
+                continue;
+            }
+            if node.count > 0 {
+                for &i in &self.order[node.start..node.start + node.count] {
+                    let max_t = closest.as_ref().map_or(t_range.1, |c| c.1.next_up());
+                    let Some((t, data)) = hit(i, (t_range.0, max_t)) else { continue };
+                    if closest.as_ref().is_none_or(|c| t < c.1 || (t == c.1 && i < c.0)) {
+                        closest = Some((i, t, data));
+                    }
+                }
+            } else if negative[node.axis] {
+                stack.push(n + 1);  // visit the far side last
+                stack.push(node.second);
+            } else {
+                stack.push(node.second);
+                stack.push(n + 1);
+            }
+        }
+        closest
+    }
+}
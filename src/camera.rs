@@ -1,37 +1,220 @@
-use crate::utils::{Point3, Color3, Ray, UNIT_X, UNIT_Z, RayHit, MINIMUM, INFINITY};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use crate::utils::{Point3, Color3, Ray, ORIGIN, UNIT_Y, UNIT_Z, BLACK, WHITE, MINIMUM, INFINITY};
+use crate::error::RaytraceError;
 use crate::object::{World};
-use rand::prelude::*;
+use crate::image::Framebuffer;
+use rand::{Rng, RngCore};
+use crate::random::Pcg32;
 
 #[derive(Debug, Clone)]
 struct Viewport {
-    // viewport = (positon vector to top left corner, position vector to bottom right corner)
-    position: (Point3, Point3),
+    projection: Projection,
+    basis: (Point3, Point3, Point3),  // u right, v up, w backwards
+    size: (f64, f64),  // image width and height in pixels
+    half_fov: f64,     // vertical, in radians
     dx: Point3, 
     dy: Point3,
-    first_pixel_center: Point3,
-    rng: ThreadRng
+    first_pixel_center: Point3,  // on the plane in focus, or for orthographic where rays start
+    lens_u: Point3,  // lens radius along the camera's right and up, zero for a pinhole
+    lens_v: Point3
 }
 
 impl Viewport {
-    pub fn construct(position: (Point3, Point3), dx: Point3, dy: Point3, first_pixel_center: Point3) -> Viewport {
-        let v = Viewport {
-            position, dx, dy, first_pixel_center, rng: rand::rng()
+    // None where the projection covers no direction (outside the fisheye circle)
+    pub fn produce_ray(&self, i: i32, j: i32, orig: Point3, rng: &mut dyn RngCore) -> Option<Ray> {
+        let offset_x: f64 = rng.random_range(-0.5..0.5);
+        let offset_y: f64 = rng.random_range(-0.5..0.5);
+        let (x, y) = (i as f64 + offset_x, j as f64 + offset_y);
+        let (u, v, w) = self.basis;
+        // position relative to the image center, with the image height going from -1 to 1
+        let (width, height) = self.size;
+        let (px, py) = ((2.0*x + 1.0 - width) / height, (height - 2.0*y - 1.0) / height);
+
+        match self.projection {
+            Projection::Perspective => {
+                // thin lens: start anywhere on the lens disk, aiming at the same point in focus
+                let (lens_x, lens_y) = if self.lens_u.norm_square() == 0.0 { (0.0, 0.0) } else {
+                    loop {
+                        let (x, y): (f64, f64) = (rng.random_range(-1.0..1.0), rng.random_range(-1.0..1.0));
+                        if x*x + y*y < 1.0 {
+                            break (x, y);
+                        }
+                    }
+                };
+                let start = orig + self.lens_u*lens_x + self.lens_v*lens_y;
+                Some(Ray::construct(
+                    (self.first_pixel_center + 
+                    self.dx*x + 
+                    self.dy*y - start).unit_vector(), 
+                    start
+                ))
+            },
+            Projection::Orthographic(_) => {
+                Some(Ray::construct(-w, self.first_pixel_center + self.dx*x + self.dy*y))
+            },
+            Projection::Fisheye => {
+                // equidistant: the angle off the viewing direction grows linearly with the distance from the center
+                let theta = (px*px + py*py).sqrt() * self.half_fov;
+                if theta > std::f64::consts::PI {
+                    return None;
+                }
+                let phi = py.atan2(px);
+                Some(Ray::construct(u*(theta.sin()*phi.cos()) + v*(theta.sin()*phi.sin()) - w*theta.cos(), orig))
+            },
+            Projection::Equirectangular => {
+                // longitude across the image width, latitude up the height
+                let longitude = px * height / width * std::f64::consts::PI;
+                let latitude = py * std::f64::consts::FRAC_PI_2;
+                Some(Ray::construct(
+                    u*(latitude.cos()*longitude.sin()) + v*latitude.sin() - w*(latitude.cos()*longitude.cos()),
+                    orig
+                ))
+            }
+        }
+    }
+}
+
+/// How directions in the scene map onto the image.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    Perspective,
+    Orthographic(Option<f64>),  // height of the view in scene units; None for what vfov shows at look_at
+    Fisheye,                    // equidistant, with vfov across the image height, up to 360 degrees
+    Equirectangular             // all directions, longitude across the width and latitude up the height (use 2:1)
+}
+
+impl Projection {
+    /// The projection by name, as written in scene files and on the command line.
+    pub fn from_name(name: &str) -> Option<Projection> {
+        match name {
+            "perspective" => Some(Projection::Perspective),
+            "orthographic" => Some(Projection::Orthographic(None)),
+            "fisheye" => Some(Projection::Fisheye),
+            "equirectangular" => Some(Projection::Equirectangular),
+            _ => None
+        }
+    }
+}
+
+/// Where the camera is, where it looks and how wide it sees.
+#[derive(Copy, Clone, Debug)]
+pub struct View {
+    pub look_from: Point3,
+    pub look_at: Point3,
+    pub vup: Point3,  // up in the image, need not be orthogonal to the viewing direction
+    pub vfov: f64,    // vertical field of view, in degrees
+    pub projection: Projection,
+    pub aperture: Aperture,  // perspective only
+    pub focus: Focus,
+    pub shutter: (f64, f64)  // open and close time; moving objects go from time 0 to 1
+}
+
+/// Size of the lens; anything but a zero radius blurs what is out of focus.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Aperture {
+    Radius(f64),  // in scene units, 0 for a pinhole camera
+    FStop(f64)    // focal length over diameter, for a 35mm camera with the same field of view and 1 unit = 1 m
+}
+
+/// How far away the camera focuses.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Focus {
+    LookAt,         // on the look_at point
+    Distance(f64),  // along the viewing direction
+    Auto            // on whatever is seen through the center of the image
+}
+
+impl View {
+    /// Looking down +Y with +Z up, as the camera always did before it could be pointed.
+    pub const DEFAULT: View = View {
+        look_from: ORIGIN, look_at: UNIT_Y, vup: UNIT_Z, vfov: 90.0,
+        projection: Projection::Perspective,
+        aperture: Aperture::Radius(0.0),
+        focus: Focus::LookAt,
+        shutter: (0.0, 1.0)
+    };
+
+    /// A pinhole camera; set aperture and focus for depth of field.
+    pub fn construct(look_from: Point3, look_at: Point3, vup: Point3, vfov: f64) -> View {
+        View {look_from, look_at, vup, vfov, ..View::DEFAULT}
+    }
+    pub fn lens_radius(&self) -> Result<f64, RaytraceError> {
+        match self.aperture {
+            Aperture::Radius(r) if r.is_finite() && r >= 0.0 => Ok(r),
+            Aperture::FStop(n) if n.is_finite() && n > 0.0 => {
+                // a 24mm high film frame behind a lens giving the same vertical field of view
+                let focal_length = 0.012 / (self.vfov.to_radians() / 2.0).tan();
+                Ok(focal_length / n / 2.0)
+            },
+            Aperture::Radius(value) | Aperture::FStop(value) => Err(RaytraceError::InvalidAperture(value))
+        }
+    }
+    /// Orthonormal camera basis (u right, v up, w backwards), or why there is none.
+    pub fn basis(&self) -> Result<(Point3, Point3, Point3), RaytraceError> {
+        let valid_fov = match self.projection {
+            Projection::Perspective | Projection::Orthographic(None) => self.vfov > 0.0 && self.vfov < 180.0,
+            Projection::Fisheye => self.vfov > 0.0 && self.vfov <= 360.0,
+            Projection::Orthographic(Some(_)) | Projection::Equirectangular => true  // not used
         };
-        v
-    }
-    pub fn produce_ray(&mut self, i: i32, j: i32, orig: Point3) -> Ray {
-        let offset_x: f64 = self.rng.random_range(-0.5..0.5);
-        let offset_y: f64 = self.rng.random_range(-0.5..0.5);
-        Ray::construct(
-            (self.first_pixel_center + 
-            self.dx*(i as f64 + offset_x) + 
-            self.dy*(j as f64 + offset_y) - orig).unit_vector(), 
-            orig
-        )
+        if !valid_fov {
+            return Err(RaytraceError::InvalidFieldOfView(self.vfov));
+        }
+        let w = (self.look_from - self.look_at).unit_vector();
+        let u = self.vup.cross(w).unit_vector();
+        if [w, u].iter().any(|p| !(p.x.is_finite() && p.y.is_finite() && p.z.is_finite())) {
+            return Err(RaytraceError::DegenerateView);
+        }
+        Ok((u, w.cross(u), w))
+    }
+}
+
+/// Render settings as given on the command line or in a scene file; None means not given.
+#[derive(Clone, Debug, Default)]
+pub struct RenderSettings {
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub aspect: Option<f64>,  // width / height, used when only one of them is given
+    pub pixel_samples: Option<i32>,
+    pub scatter_depth: Option<i32>,
+    pub seed: Option<u64>,  // the same seed and settings render the same image, on any number of threads
+    pub threads: Option<usize>
+}
+
+impl RenderSettings {
+    pub const DEFAULT_WIDTH: i32 = 512;
+    pub const DEFAULT_ASPECT: f64 = 16.0 / 9.0;
+    pub const DEFAULT_PIXEL_SAMPLES: i32 = 3;  // for anti-aliasing (1 => no anti-aliasing)
+    pub const DEFAULT_SCATTER_DEPTH: i32 = 10;
+
+    /// Each setting from `self`, or from `fallback` where `self` doesn't give it.
+    pub fn or(self, fallback: RenderSettings) -> RenderSettings {
+        RenderSettings {
+            width: self.width.or(fallback.width),
+            height: self.height.or(fallback.height),
+            aspect: self.aspect.or(fallback.aspect),
+            pixel_samples: self.pixel_samples.or(fallback.pixel_samples),
+            scatter_depth: self.scatter_depth.or(fallback.scatter_depth),
+            seed: self.seed.or(fallback.seed),
+            threads: self.threads.or(fallback.threads)
+        }
+    }
+    pub fn resolution(&self) -> (i32, i32) {
+        let aspect = self.aspect.unwrap_or(Self::DEFAULT_ASPECT);
+        match (self.width, self.height) {
+            (Some(width), Some(height)) => (width, height),
+            (None, Some(height)) => (((height as f64 * aspect) as i32).max(1), height),
+            (width, None) => {
+                let width = width.unwrap_or(Self::DEFAULT_WIDTH);
+                (width, ((width as f64 / aspect) as i32).max(1))
+            }
+        }
     }
 }
 
 // #[derive(Debug)]
+/// Renders a [`World`] into a [`Framebuffer`], tile by tile over all threads.
 pub struct Camera<'a> {
     pub image_height: i32,
     pub image_width: i32,
@@ -39,63 +222,363 @@ pub struct Camera<'a> {
     viewport: Viewport,
     pub world: &'a World,
     pub pixel_samples: i32,
-    pub scatter_depth: i32
+    pub scatter_depth: i32,
+    pub threads: usize,  // 0 => one per core
+    pub seed: u64,       // every pixel sample draws from its own Pcg32 stream, derived from the seed and
+                         // the pixel and sample numbers, so the image doesn't depend on the tiling or threads
+    pub shutter: (f64, f64)  // every sample is taken at a random time in between
 }
 
 impl Camera<'_> {
-    fn header_ascii_ppm(&self) {
-        println!("P3\n{} {}", self.image_width, self.image_height);
-        println!("255");
-    }
-    pub fn construct<'a>(world: &'a World, width: i32, height: i32, camera: Point3, viewport_center: Point3, viewport_diagonal: Point3, pixel_samples: i32, scatter_depth: i32) -> Camera<'a> {
-        let position = (viewport_center - viewport_diagonal / 2, 
-                        viewport_center + viewport_diagonal / 2);
-        let dx = viewport_diagonal*UNIT_X / width;
-        let dy = viewport_diagonal*UNIT_Z / height;
-        Camera {
+    const TILE_SIZE: i32 = 16;
+
+    /// A camera rendering `view` into `width` by `height` pixels; the aspect ratio of the view is
+    /// width / height, for square pixels.
+    pub fn construct<'a>(world: &'a World, width: i32, height: i32, view: &View) -> Result<Camera<'a>, RaytraceError> {
+        let (u, v, w) = view.basis()?;
+        let (open, close) = view.shutter;
+        if !(open.is_finite() && close.is_finite() && open <= close) {
+            return Err(RaytraceError::InvalidShutter(open, close));
+        }
+        let lens_radius = view.lens_radius()?;
+        let focus_distance = match view.focus {
+            Focus::LookAt => (view.look_at - view.look_from).norm(),
+            Focus::Distance(d) if d.is_finite() && d > 0.0 => d,
+            Focus::Distance(d) => return Err(RaytraceError::InvalidFocusDistance(d)),
+            Focus::Auto => match world.hit(&Ray::construct(-w, view.look_from).with_time(view.shutter.0), (MINIMUM, INFINITY)) {
+                Some(hit) => hit.t,
+                None => (view.look_at - view.look_from).norm()  // nothing there, so nothing to be sharp
+            }
+        };
+        let (width_, height_) = (width as f64, height as f64);
+        let (dx, dy, first_pixel_center) = match view.projection {
+            Projection::Perspective => {
+                // the viewport lies on the plane in focus
+                let viewport_height = 2.0 * (view.vfov.to_radians() / 2.0).tan() * focus_distance;
+                let dx = u * viewport_height / height_;
+                let dy = -v * viewport_height / height_;  // rows go down
+                (dx, dy, view.look_from - w*focus_distance - dx*(width_ - 1.0)/2.0 - dy*(height_ - 1.0)/2.0)
+            },
+            Projection::Orthographic(view_height) => {
+                let view_height = view_height.unwrap_or(2.0 * (view.vfov.to_radians() / 2.0).tan() * (view.look_at - view.look_from).norm());
+                if !(view_height.is_finite() && view_height > 0.0) {
+                    return Err(RaytraceError::InvalidViewHeight(view_height));
+                }
+                let dx = u * view_height / height_;
+                let dy = -v * view_height / height_;
+                (dx, dy, view.look_from - dx*(width_ - 1.0)/2.0 - dy*(height_ - 1.0)/2.0)
+            },
+            Projection::Fisheye | Projection::Equirectangular => (ORIGIN, ORIGIN, view.look_from)
+        };
+        let perspective = view.projection == Projection::Perspective;
+        let viewport = Viewport {
+            projection: view.projection,
+            basis: (u, v, w),
+            size: (width_, height_),
+            half_fov: view.vfov.to_radians() / 2.0,
+            dx, dy, first_pixel_center,
+            lens_u: if perspective { u * lens_radius } else { ORIGIN },
+            lens_v: if perspective { v * lens_radius } else { ORIGIN }
+        };
+        Ok(Camera {
             image_width: width,
             image_height: height,
-            camera,
-            viewport: Viewport::construct(position, dx, dy, position.0 + (dx + dy)/2),
-            world, pixel_samples, scatter_depth
+            camera: view.look_from,
+            viewport,
+            world,
+            pixel_samples: RenderSettings::DEFAULT_PIXEL_SAMPLES,
+            scatter_depth: RenderSettings::DEFAULT_SCATTER_DEPTH,
+            threads: 0,
+            seed: 0,
+            shutter: view.shutter
+        })
+    }
+    fn render_tile(&self, tile: (i32, i32, i32, i32)) -> Vec<Color3> {
+        let (x0, y0, x1, y1) = tile;
+        let mut pixels = Vec::with_capacity(((x1 - x0) * (y1 - y0)) as usize);
+        for j in y0..y1 {
+            for i in x0..x1 {
+                let mut pixel = BLACK;
+                for sample in 0..self.pixel_samples {  // anti-aliasing
+                    let rng = &mut Pcg32::for_sample(self.seed, i as u32, j as u32, sample as u32);
+                    let (open, close) = self.shutter;
+                    let time = if open < close { rng.random_range(open..close) } else { open };
+                    if let Some(ray) = self.viewport.produce_ray(i, j, self.camera, rng) {
+                        pixel += self.ray_color(ray.with_time(time), rng);
+                    }
+                }
+                pixels.push(pixel / self.pixel_samples as f64);
+            }
         }
+        pixels
     }
-    pub fn render_ascii_ppm(&mut self) {
-        self.header_ascii_ppm();
+    /// Renders the image in tiles over all threads.
+    pub fn render(&self) -> Framebuffer {
+        let mut tiles = Vec::new();
+        for y0 in (0..self.image_height).step_by(Self::TILE_SIZE as usize) {
+            for x0 in (0..self.image_width).step_by(Self::TILE_SIZE as usize) {
+                tiles.push((x0, y0, (x0 + Self::TILE_SIZE).min(self.image_width), (y0 + Self::TILE_SIZE).min(self.image_height)));
+            }
+        }
+        let threads = match self.threads {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n
+        };
+        let mut image = Framebuffer::construct(self.image_width as usize, self.image_height as usize);
+        let next_tile = AtomicUsize::new(0);
+        let (sender, receiver) = mpsc::channel();
+
         eprintln!();
-        for j in 0..self.image_height {
-            for i in 0..self.image_width {
-                let mut pixel = Point3 {x: 0.0, y: 0.0, z: 0.0};
-                for _ in 0..self.pixel_samples {  // anti-aliasing
-                    let ray = self.viewport.produce_ray(i, j, self.camera);
-                    pixel += self.ray_color(ray).to_point3();
+        thread::scope(|scope| {
+            for _ in 0..threads.min(tiles.len()) {
+                let sender = sender.clone();
+                let (tiles, next_tile) = (&tiles, &next_tile);
+                scope.spawn(move || {
+                    loop {
+                        let n = next_tile.fetch_add(1, Ordering::Relaxed);
+                        let Some(&tile) = tiles.get(n) else { break };
+                        if sender.send((tile, self.render_tile(tile))).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(sender);
+
+            for (done, ((x0, y0, x1, _), pixels)) in receiver.iter().enumerate() {
+                let width = (x1 - x0) as usize;
+                for (row, chunk) in pixels.chunks(width).enumerate() {
+                    let start = ((y0 as usize + row) * self.image_width as usize) + x0 as usize;
+                    image.pixels[start..start + width].copy_from_slice(chunk);
                 }
-                pixel /= self.pixel_samples as f64;
-                Color3::construct(pixel.x, pixel.y, pixel.z).print_out();
+                eprint!("\r{:.1}% done: {} tiles of {} total", 100.0*(done + 1) as f64/tiles.len() as f64, done + 1, tiles.len());
             }
-            eprint!("\r{}% done: {} rows of {} total", 100.0*j as f64/self.image_height as f64, j, self.image_height);
-        }
+        });
         eprintln!();
         eprintln!("Done");
+        image
     }
-    pub fn ray_color(&self, ray: Ray) -> Color3 {
-        let t = 0.5*(ray.A.z + 1.0);
-        let init = Point3 {x: 0.4, y: 0.6, z: 1.0};
-        let end = Point3 {x: 1.0, y: 1.0, z: 1.0};
-        let lerp = end * (1.0 - t) + init * t;
-        let background = Color3::construct(lerp.x, lerp.y, lerp.z);
-
-        let scattered_ray = ray.clone();
-        let mut color = background;
-        let mut hit = self.world.hit(&scattered_ray, (MINIMUM, INFINITY));
+    pub fn render_ascii_ppm(&self) {
+        self.render().print_ascii_ppm();
+    }
+    /// Path tracing: the light reaching the camera along `ray`, as the emission met along the
+    /// scattered path, weighted by what the surfaces before it let through. At surfaces with a
+    /// bsdf, emitting objects are also sampled directly; light found either way is weighted by
+    /// multiple importance sampling (the balance heuristic), so it is counted once overall. The
+    /// world's point, spot and directional lights are only reached through shadow rays.
+    pub fn ray_color(&self, ray: Ray, rng: &mut dyn RngCore) -> Color3 {
+        let mut radiance = BLACK;
+        let mut throughput = WHITE;
+        let mut ray = ray;
+        let mut scatter_pdf: Option<f64> = None;  // of the last bounce; None from the camera or a specular one
 
         for _ in 0..self.scatter_depth {
-            let RayHit::Hit {material, ..} = hit else { break; };
-            color = material.attenuate(color);
-            let Some(scattered_ray) = material.scatter(&scattered_ray, &hit) else { break; };
-            hit = self.world.hit(&scattered_ray, (MINIMUM, INFINITY));
+            let Some(hit) = self.world.hit(&ray, (MINIMUM, INFINITY)) else {
+                // an emitting background, like an environment map, is sampled as a light too
+                let weight = match scatter_pdf {
+                    Some(p_scatter) if p_scatter > 0.0 && self.world.background.is_emitter() =>
+                        p_scatter / (p_scatter + self.world.emitter_pdf(&ray)),
+                    _ => 1.0
+                };
+                radiance += throughput * self.world.background(&ray) * weight;
+                break;
+            };
+            if hit.material.is_emitter() {
+                let weight = match scatter_pdf {
+                    Some(p_scatter) if p_scatter > 0.0 => p_scatter / (p_scatter + self.world.emitter_pdf(&ray)),
+                    _ => 1.0
+                };
+                radiance += throughput * hit.material.emitted(&hit) * weight;
+            }
+            if let Some(direction) = self.world.sample_emitter(hit.point, ray.time, rng)
+                && let Some((f, p_scatter)) = hit.material.bsdf(&ray, &hit, direction) {
+                let shadow_ray = Ray::construct(direction, hit.point).with_time(ray.time);
+                let p_light = self.world.emitter_pdf(&shadow_ray);
+                if p_light > 0.0 {
+                    let emitted = match self.world.hit(&shadow_ray, (MINIMUM, INFINITY)) {
+                        Some(light_hit) => light_hit.material.emitted(&light_hit),
+                        None if self.world.background.is_emitter() => self.world.background(&shadow_ray),
+                        None => BLACK
+                    };
+                    // (p_light / (p_light + p_scatter)) / p_light
+                    radiance += throughput * f * emitted / (p_light + p_scatter);
+                }
+            }
+            // lights that are not objects can only be sampled, so they need no weighting
+            for light in self.world.lights() {
+                if let Some(sample) = light.sample(hit.point, (rng.random(), rng.random()))
+                    && let Some((f, _)) = hit.material.bsdf(&ray, &hit, sample.direction) {
+                    let shadow_ray = Ray::construct(sample.direction, hit.point).with_time(ray.time);
+                    if self.world.hit(&shadow_ray, (MINIMUM, sample.distance)).is_none() {
+                        radiance += throughput * f * sample.radiance;
+                    }
+                }
+            }
+            let Some(scattered) = hit.material.scatter(&ray, &hit, rng) else { break; };
+            scatter_pdf = hit.material.bsdf(&ray, &hit, scattered.A).map(|(_, pdf)| pdf);
+            throughput = hit.material.attenuate(throughput);
+            ray = scattered;
+        }
+
+        radiance
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::{Sphere, Plane};
+    use crate::material::{Diffuse, Metallic, LightSource};
+
+    // n rays the camera sends through pixel (i, j)
+    fn rays(camera: &Camera, i: i32, j: i32, n: usize) -> Vec<Ray> {
+        let mut rng = Pcg32::construct(9, 0);
+        (0..n).filter_map(|_| camera.viewport.produce_ray(i, j, camera.camera, &mut rng)).collect()
+    }
+
+    #[test]
+    fn look_at_views_point_anywhere() {
+        let world = World::construct(Vec::new());
+        let look_from = Point3 {x: 1.0, y: 2.0, z: 3.0};
+        let forward = Point3 {x: 0.6, y: 0.0, z: -0.8};
+        let view = View::construct(look_from, look_from + forward * 5.0, UNIT_Z, 60.0);
+        let (u, v, w) = view.basis().unwrap();
+        assert!((w + forward).norm() < 1e-12);
+        assert!((u - Point3 {x: 0.0, y: -1.0, z: 0.0}).norm() < 1e-12);
+        assert!((v - Point3 {x: 0.8, y: 0.0, z: 0.6}).norm() < 1e-12);
+
+        // the image plane one unit ahead is 2 tan(30 degrees) high; pixels are 1/51 of that
+        let camera = Camera::construct(&world, 101, 51, &view).unwrap();
+        let half_height = 30.0f64.to_radians().tan();
+        for (i, j) in [(50, 25), (0, 0), (100, 50), (100, 0), (7, 31)] {
+            let (px, py) = ((2*i + 1 - 101) as f64 / 51.0, (51 - 2*j - 1) as f64 / 51.0);
+            let expected = (forward + u * (px * half_height) + v * (py * half_height)).unit_vector();
+            for ray in rays(&camera, i, j, 20) {
+                assert_eq!((ray.B.x, ray.B.y, ray.B.z), (1.0, 2.0, 3.0));
+                assert!(ray.A.dot(expected) > 0.015f64.cos(), "pixel ({}, {})", i, j);
+            }
         }
 
-        color
+        assert!(matches!(View::construct(look_from, look_from + UNIT_Z, UNIT_Z, 60.0).basis(), Err(RaytraceError::DegenerateView)));
+        assert!(matches!(View::construct(look_from, ORIGIN, UNIT_Z, 180.0).basis(), Err(RaytraceError::InvalidFieldOfView(_))));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn lens_rays_meet_on_the_plane_in_focus() {
+        // a wall 5 units ahead, for autofocus to find
+        let wall = Plane::construct(Point3 {x: 0.0, y: 5.0, z: 0.0}, UNIT_Y, Box::new(Diffuse::construct(0.5, 0.5, 0.5).unwrap())).unwrap();
+        let world = World::construct(vec![Box::new(wall)]);
+        for (focus, distance) in [(Focus::Distance(3.0), 3.0), (Focus::Auto, 5.0), (Focus::LookAt, 1.0)] {
+            let view = View {aperture: Aperture::Radius(0.2), focus, ..View::DEFAULT};
+            let camera = Camera::construct(&world, 64, 48, &view).unwrap();
+            // where rays cross the plane y = depth, as (x, z)
+            let crossing = |ray: &Ray, depth: f64| {
+                let p = ray.produce((depth - ray.B.y) / ray.A.y);
+                (p.x, p.z)
+            };
+            // how far apart points are, across or up
+            let spread = |points: Vec<(f64, f64)>| {
+                let range = |values: Vec<f64>| values.iter().fold(f64::MIN, |a, &b| a.max(b)) - values.iter().fold(f64::MAX, |a, &b| a.min(b));
+                range(points.iter().map(|p| p.0).collect()).max(range(points.iter().map(|p| p.1).collect()))
+            };
+            for (i, j) in [(32, 24), (5, 40)] {
+                let rays = rays(&camera, i, j, 200);
+                // starting all over the lens, around the camera and square to the view
+                assert!(rays.iter().all(|ray| ray.B.y == 0.0 && ray.B.norm() <= 0.2));
+                assert!(spread(rays.iter().map(|ray| (ray.B.x, ray.B.z)).collect()) > 0.3);
+                // on the plane in focus they stay within the pixel, 2 tan(45 degrees) / 48 of the distance
+                let pixel = 2.0 * distance / 48.0;
+                assert!(spread(rays.iter().map(|ray| crossing(ray, distance)).collect()) <= pixel * (1.0 + 1e-9));
+                assert!(spread(rays.iter().map(|ray| crossing(ray, distance * 2.0)).collect()) > 0.3);
+            }
+        }
+        // a 50mm lens on a 24mm high frame at f/2.5 is 20mm across
+        let view = View {vfov: 2.0 * 0.24f64.atan().to_degrees(), aperture: Aperture::FStop(2.5), ..View::DEFAULT};
+        assert!((view.lens_radius().unwrap() - 0.01).abs() < 1e-12);
+    }
+
+    #[test]
+    fn projections_map_pixels_to_known_directions() {
+        let world = World::construct(Vec::new());
+        // every ray through pixel (i, j) within degrees of direction
+        let within = |camera: &Camera, i: i32, j: i32, direction: Point3, degrees: f64| {
+            rays(camera, i, j, 20).iter().all(|ray| ray.A.dot(direction.unit_vector()) >= degrees.to_radians().cos())
+        };
+
+        // a degree per pixel: straight ahead in the middle, right (+X) a quarter turn on, up along the top
+        let view = View {projection: Projection::Equirectangular, ..View::DEFAULT};
+        let camera = Camera::construct(&world, 360, 180, &view).unwrap();
+        assert!(within(&camera, 180, 90, UNIT_Y, 1.5));
+        assert!(within(&camera, 270, 90, Point3 {x: 1.0, y: 0.0, z: 0.0}, 1.5));
+        assert!(within(&camera, 0, 90, -UNIT_Y, 1.5));
+        assert!(within(&camera, 100, 0, UNIT_Z, 1.5));
+
+        // the fov across the height, and the angle growing in step with the distance from the middle,
+        // 1.8 degrees per pixel
+        let view = View {projection: Projection::Fisheye, vfov: 180.0, ..View::DEFAULT};
+        let camera = Camera::construct(&world, 101, 101, &view).unwrap();
+        assert!(within(&camera, 50, 50, UNIT_Y, 1.5));
+        assert!(within(&camera, 100, 50, Point3 {x: 1.0, y: 0.0, z: 0.0}, 2.5));
+        assert!(within(&camera, 50, 25, Point3 {x: 0.0, y: 1.0, z: 1.0}, 2.5));
+        // at 360 degrees the circle reaches straight back, and the corners are outside it
+        let view = View {projection: Projection::Fisheye, vfov: 360.0, ..View::DEFAULT};
+        let camera = Camera::construct(&world, 101, 101, &view).unwrap();
+        assert!(within(&camera, 100, 50, -UNIT_Y, 5.0));
+        assert!(rays(&camera, 0, 0, 20).is_empty());
+
+        // parallel rays from a view 4 high, 0.4 per pixel, centered on look_from
+        let view = View {projection: Projection::Orthographic(Some(4.0)), ..View::DEFAULT};
+        let camera = Camera::construct(&world, 20, 10, &view).unwrap();
+        for (i, j) in [(0, 0), (19, 9), (12, 3)] {
+            let center = (-3.8 + 0.4 * i as f64, 1.8 - 0.4 * j as f64);
+            for ray in rays(&camera, i, j, 20) {
+                assert!((ray.A - UNIT_Y).norm() < 1e-12);
+                assert!(ray.B.y == 0.0 && (ray.B.x - center.0).abs() <= 0.2 + 1e-12 && (ray.B.z - center.1).abs() <= 0.2 + 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn emitters_add_their_radiance() {
+        // inside a glowing ball, lighting a gray floor
+        let glow = || Box::new(LightSource::construct(0.5, 0.25, 1.0, 2.0).unwrap());
+        let floor = || Box::new(Plane::construct(Point3 {x: 0.0, y: 0.0, z: -1.0}, UNIT_Z, Box::new(Diffuse::construct(0.5, 0.5, 0.5).unwrap())).unwrap());
+        let world = World::construct(vec![Box::new(Sphere::construct(ORIGIN, 100.0, glow()).unwrap()), floor()]);
+        let camera = Camera::construct(&world, 8, 8, &View::DEFAULT).unwrap();
+        let mut rng = Pcg32::construct(4, 0);
+        let color = camera.ray_color(Ray::construct(Point3 {x: 0.3, y: 1.0, z: 0.2}, ORIGIN), &mut rng);
+        assert_eq!((color.r, color.g, color.b), (1.0, 0.5, 2.0));
+
+        // the floor sees the glow all over its upper half, and passes on half of it
+        let samples = 4000;
+        let mut total = BLACK;
+        for _ in 0..samples {
+            total += camera.ray_color(Ray::construct(Point3 {x: 0.0, y: 1.0, z: -1.0}, ORIGIN), &mut rng);
+        }
+        let mean = total / samples as f64;
+        for (measured, expected) in [(mean.r, 0.5), (mean.g, 0.25), (mean.b, 1.0)] {
+            assert!((measured - expected).abs() < 0.02 * expected, "{} vs {}", measured, expected);
+        }
+
+        // a one sided light gives nothing off its back
+        let world = World::construct(vec![Box::new(Sphere::construct(ORIGIN, 100.0, Box::new(glow().one_sided())).unwrap())]);
+        let camera = Camera::construct(&world, 8, 8, &View::DEFAULT).unwrap();
+        let color = camera.ray_color(Ray::construct(UNIT_Y, ORIGIN), &mut rng);
+        assert_eq!((color.r, color.g, color.b), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn the_seed_alone_decides_the_image() {
+        let world = World::construct(vec![
+            Box::new(Sphere::construct(Point3 {x: 0.0, y: 2.0, z: 0.0}, 0.5, Box::new(Metallic::construct(0.8, 0.6, 0.2, 0.3).unwrap())).unwrap()),
+            Box::new(Plane::construct(Point3 {x: 0.0, y: 0.0, z: -0.5}, UNIT_Z, Box::new(Diffuse::construct(0.5, 0.5, 0.5).unwrap())).unwrap())
+        ]);
+        let render = |seed: u64, threads: usize| {
+            let mut camera = Camera::construct(&world, 40, 24, &View::DEFAULT).unwrap();
+            (camera.seed, camera.threads, camera.pixel_samples) = (seed, threads, 2);
+            camera.render().pixels.iter().map(|c| [c.r.to_bits(), c.g.to_bits(), c.b.to_bits()]).collect::<Vec<_>>()
+        };
+        let image = render(5, 1);
+        assert_eq!(render(5, 3), image);
+        assert_ne!(render(6, 1), image);
+    }
+}
@@ -0,0 +1,294 @@
+use std::path::PathBuf;
+use raytracing::{Point3, ImageFormat, RenderSettings, Scene, Projection, Aperture, Focus};
+
+pub const USAGE: &str = "\
+Usage: raytracing [OPTIONS]
+
+Renders a scene and writes the image to --output, or as ASCII PPM to stdout.
+
+Options:
+  -W, --width <PIXELS>      image width [default: 512, or height * aspect]
+  -H, --height <PIXELS>     image height [default: width / aspect]
+  -a, --aspect <RATIO>      aspect ratio as W:H or a number [default: 16:9]
+  -s, --samples <N>         samples per pixel [default: 3]
+  -d, --depth <N>           maximum scatter depth [default: 10]
+  -o, --output <PATH>       output image; the format comes from the extension
+                            (ppm, png, bmp, tga, pfm, hdr, exr)
+  -f, --format <FORMAT>     output format, overriding the extension
+  -S, --scene <PATH>        scene file, or a Wavefront .obj, to render instead of
+                            the demo scene; the options above override its settings
+  -c, --camera <X,Y,Z>      camera position, keeping the viewing direction unless
+                            --look-at is given [default: 0,0,0]
+      --look-at <X,Y,Z>     point the camera looks at [default: 0,1,0]
+      --up <X,Y,Z>          up direction of the image [default: 0,0,1]
+      --fov <DEGREES>       vertical field of view, up to 360 for fisheye [default: 90]
+      --projection <NAME>   perspective, orthographic, fisheye (equidistant) or
+                            equirectangular (360 degree panorama) [default: perspective]
+      --view-height <H>     height the orthographic projection shows, in scene units
+                            [default: what the field of view shows at the look-at point]
+      --aperture <RADIUS>   lens radius, for depth of field [default: 0, a pinhole]
+      --f-stop <N>          lens aperture as an f-number, instead of --aperture
+      --focus <DISTANCE>    focus distance, or 'auto' to focus on the center of the
+                            image [default: the distance to the look-at point]
+      --shutter <T0,T1>     time interval the samples are taken in, where moving
+                            objects go from time 0 to 1 [default: 0,1]
+      --seed <N>            random seed; the same seed renders the same image [default: 0]
+  -j, --threads <N>         render threads, 0 for one per core [default: 0]
+  -h, --help                print this help";
+
+// every option taking a value
+const OPTIONS: [&str; 30] = [
+    "-W", "--width", "-H", "--height", "-a", "--aspect", "-s", "--samples", "-d", "--depth",
+    "-o", "--output", "-f", "--format", "-S", "--scene", "-c", "--camera", "--look-at", "--up", "--fov",
+    "--projection", "--view-height", "--aperture", "--f-stop", "--focus", "--shutter", "--seed", "-j", "--threads"
+];
+
+#[derive(Clone, Debug)]
+pub struct Settings {
+    pub render: RenderSettings,
+    pub output: Option<PathBuf>,
+    pub format: Option<ImageFormat>,
+    pub scene: Option<PathBuf>,
+    pub camera: Option<Point3>,
+    pub look_at: Option<Point3>,
+    pub up: Option<Point3>,
+    pub fov: Option<f64>,
+    pub projection: Option<Projection>,
+    pub view_height: Option<f64>,
+    pub aperture: Option<Aperture>,
+    pub focus: Option<Focus>,
+    pub shutter: Option<(f64, f64)>
+}
+
+#[derive(Clone, Debug)]
+pub enum Command {
+    Help,
+    Render(Box<Settings>)
+}
+
+fn value<T: std::str::FromStr>(option: &str, text: &str) -> Result<T, String> {
+    text.parse().map_err(|_| format!("invalid value '{}' for {}", text, option))
+}
+
+fn positive(option: &str, text: &str) -> Result<i32, String> {
+    match value(option, text)? {
+        n if n > 0 => Ok(n),
+        _ => Err(format!("{} must be at least 1, got '{}'", option, text))
+    }
+}
+
+fn aspect(text: &str) -> Result<f64, String> {
+    let ratio = match text.split_once(':') {
+        Some((w, h)) => value::<f64>("--aspect", w)? / value::<f64>("--aspect", h)?,
+        None => value("--aspect", text)?
+    };
+    if !ratio.is_finite() || ratio <= 0.0 {
+        return Err(format!("invalid aspect ratio '{}'", text));
+    }
+    Ok(ratio)
+}
+
+fn point(option: &str, text: &str) -> Result<Point3, String> {
+    let parts: Vec<&str> = text.split(',').collect();
+    let [x, y, z] = parts[..] else { return Err(format!("{} takes X,Y,Z, got '{}'", option, text)) };
+    Ok(Point3 {x: value(option, x.trim())?, y: value(option, y.trim())?, z: value(option, z.trim())?})
+}
+
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.into_iter();
+    let mut settings = Settings {
+        render: RenderSettings::default(),
+        output: None,
+        format: None,
+        scene: None,
+        camera: None,
+        look_at: None,
+        up: None,
+        fov: None,
+        projection: None,
+        view_height: None,
+        aperture: None,
+        focus: None,
+        shutter: None
+    };
+    let render = &mut settings.render;
+
+    while let Some(arg) = args.next() {
+        // both "--option value" and "--option=value"
+        let (option, inline) = match arg.split_once('=') {
+            Some((option, v)) if option.starts_with("--") => (option.to_string(), Some(v.to_string())),
+            _ => (arg.clone(), None)
+        };
+        if matches!(option.as_str(), "-h" | "--help") {
+            return Ok(Command::Help);
+        }
+        if !option.starts_with('-') {
+            return Err(format!("unexpected argument '{}'", option));
+        }
+        if !OPTIONS.contains(&option.as_str()) {
+            return Err(format!("unknown option '{}'", option));
+        }
+        let Some(text) = inline.or_else(|| args.next()) else { return Err(format!("{} needs a value", option)) };
+        match option.as_str() {
+            "-W" | "--width" => render.width = Some(positive(&option, &text)?),
+            "-H" | "--height" => render.height = Some(positive(&option, &text)?),
+            "-a" | "--aspect" => render.aspect = Some(aspect(&text)?),
+            "-s" | "--samples" => render.pixel_samples = Some(positive(&option, &text)?),
+            "-d" | "--depth" => render.scatter_depth = Some(positive(&option, &text)?),
+            "-o" | "--output" => settings.output = Some(PathBuf::from(text)),
+            "-f" | "--format" => settings.format = Some(ImageFormat::from_extension(&text)
+                .ok_or_else(|| format!("unknown image format '{}'", text))?),
+            "-S" | "--scene" => settings.scene = Some(PathBuf::from(text)),
+            "-c" | "--camera" => settings.camera = Some(point(&option, &text)?),
+            "--look-at" => settings.look_at = Some(point(&option, &text)?),
+            "--up" => settings.up = Some(point(&option, &text)?),
+            "--fov" => settings.fov = match value(&option, &text)? {
+                fov if fov > 0.0 && fov <= 360.0 => Some(fov),
+                _ => return Err(format!("--fov must be between 0 and 360 degrees, got '{}'", text))
+            },
+            "--projection" => settings.projection = Some(Projection::from_name(&text)
+                .ok_or_else(|| format!("unknown projection '{}'", text))?),
+            "--view-height" => settings.view_height = match value(&option, &text)? {
+                height if height > 0.0 => Some(height),
+                _ => return Err(format!("--view-height must be positive, got '{}'", text))
+            },
+            "--aperture" => settings.aperture = match value(&option, &text)? {
+                radius if radius >= 0.0 => Some(Aperture::Radius(radius)),
+                _ => return Err(format!("--aperture must not be negative, got '{}'", text))
+            },
+            "--f-stop" => settings.aperture = match value(&option, &text)? {
+                f_stop if f_stop > 0.0 => Some(Aperture::FStop(f_stop)),
+                _ => return Err(format!("--f-stop must be positive, got '{}'", text))
+            },
+            "--focus" => settings.focus = match text.as_str() {
+                "auto" => Some(Focus::Auto),
+                _ => match value(&option, &text)? {
+                    distance if distance > 0.0 => Some(Focus::Distance(distance)),
+                    _ => return Err(format!("--focus must be positive or 'auto', got '{}'", text))
+                }
+            },
+            "--shutter" => settings.shutter = match text.split_once(',') {
+                Some((open, close)) => match (value::<f64>(&option, open.trim())?, value::<f64>(&option, close.trim())?) {
+                    (open, close) if open <= close => Some((open, close)),
+                    _ => return Err(format!("--shutter closes before it opens: '{}'", text))
+                },
+                None => return Err(format!("--shutter takes T0,T1, got '{}'", text))
+            },
+            "--seed" => render.seed = Some(value(&option, &text)?),
+            "-j" | "--threads" => render.threads = Some(value(&option, &text)?),
+            _ => unreachable!("{} is in OPTIONS but not handled", option)
+        }
+    }
+
+    if let Some(output) = &settings.output
+        && settings.format.is_none() && ImageFormat::from_path(output).is_none() {
+        return Err(format!("cannot tell the image format of '{}', use --format", output.display()));
+    }
+    Ok(Command::Render(Box::new(settings)))
+}
+
+impl Settings {
+    // overrides the scene's camera and render settings with the ones given; --view-height goes
+    // with the projection the scene ends up with, which the scene file may have made orthographic
+    pub fn apply(&self, scene: &mut Scene) -> Result<(), String> {
+        let view = &mut scene.view;
+        if let Some(position) = self.camera {
+            view.look_at += position - view.look_from;  // moved, still looking the same way
+            view.look_from = position;
+        }
+        view.look_at = self.look_at.unwrap_or(view.look_at);
+        view.vup = self.up.unwrap_or(view.vup);
+        view.vfov = self.fov.unwrap_or(view.vfov);
+        view.projection = self.projection.unwrap_or(view.projection);
+        if let Some(height) = self.view_height {
+            match view.projection {
+                Projection::Orthographic(_) => view.projection = Projection::Orthographic(Some(height)),
+                _ => return Err("--view-height needs the orthographic projection, from --projection or the scene".to_string())
+            }
+        }
+        view.aperture = self.aperture.unwrap_or(view.aperture);
+        view.focus = self.focus.unwrap_or(view.focus);
+        view.shutter = self.shutter.unwrap_or(view.shutter);
+        scene.settings = self.render.clone().or(scene.settings.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn parse_line(line: &str) -> Result<Command, String> {
+        parse(line.split_whitespace().map(String::from))
+    }
+
+    fn settings(line: &str) -> Settings {
+        match parse_line(line) {
+            Ok(Command::Render(settings)) => *settings,
+            other => panic!("expected settings from '{}', got {:?}", line, other)
+        }
+    }
+
+    fn scene(source: &str) -> Scene {
+        Scene::parse(source, Path::new("test.scene")).unwrap()
+    }
+
+    #[test]
+    fn options_are_parsed() {
+        let settings = settings("-W 320 --aspect=4:3 -s 8 --camera 1,2,3 --projection fisheye -o out.png --focus auto --shutter 0.2,0.4 --seed 7");
+        let render = &settings.render;
+        assert_eq!((render.width, render.height, render.aspect, render.pixel_samples, render.seed), (Some(320), None, Some(4.0 / 3.0), Some(8), Some(7)));
+        assert!(matches!(settings.camera, Some(Point3 {x: 1.0, y: 2.0, z: 3.0})));
+        assert_eq!(settings.projection, Some(Projection::Fisheye));
+        assert_eq!(settings.output.as_deref(), Some(Path::new("out.png")));
+        assert_eq!(settings.focus, Some(Focus::Auto));
+        assert_eq!(settings.shutter, Some((0.2, 0.4)));
+        assert!(matches!(parse_line("-W 320 --help"), Ok(Command::Help)));
+    }
+
+    #[test]
+    fn bad_options_are_explained() {
+        for (line, message) in [
+            ("--colour red", "unknown option '--colour'"),
+            ("scene.txt", "unexpected argument 'scene.txt'"),
+            ("-W", "-W needs a value"),
+            ("-W wide", "invalid value 'wide' for -W"),
+            ("--samples 0", "--samples must be at least 1, got '0'"),
+            ("--aspect 16:0", "invalid aspect ratio '16:0'"),
+            ("--camera 1,2", "--camera takes X,Y,Z, got '1,2'"),
+            ("--fov 400", "--fov must be between 0 and 360 degrees, got '400'"),
+            ("--projection isometric", "unknown projection 'isometric'"),
+            ("--shutter 1,0", "--shutter closes before it opens: '1,0'"),
+            ("-o image.xyz", "cannot tell the image format of 'image.xyz', use --format")
+        ] {
+            assert_eq!(parse_line(line).unwrap_err(), message, "for '{}'", line);
+        }
+    }
+
+    #[test]
+    fn options_override_the_scene() {
+        let mut scene = scene("settings { width = 640  samples = 5 }\ncamera { look_from = (0, 0, 1)  look_at = (0, 2, 1)  fov = 60 }");
+        settings("-W 100 --camera 1,0,1 --fov 30").apply(&mut scene).unwrap();
+        assert_eq!((scene.settings.width, scene.settings.pixel_samples), (Some(100), Some(5)));
+        let view = &scene.view;
+        assert_eq!((view.look_from.x, view.look_at.x, view.look_at.y, view.look_at.z), (1.0, 1.0, 2.0, 1.0));
+        assert_eq!(view.vfov, 30.0);
+        assert_eq!(view.projection, Projection::Perspective);
+    }
+
+    #[test]
+    fn view_height_needs_an_orthographic_scene_or_option() {
+        let mut orthographic = scene("camera { projection = orthographic  height = 4 }");
+        settings("--view-height 2").apply(&mut orthographic).unwrap();
+        assert_eq!(orthographic.view.projection, Projection::Orthographic(Some(2.0)));
+
+        let mut perspective = scene("camera { fov = 60 }");
+        settings("--projection orthographic --view-height 3").apply(&mut perspective).unwrap();
+        assert_eq!(perspective.view.projection, Projection::Orthographic(Some(3.0)));
+
+        let message = settings("--view-height 2").apply(&mut scene("camera { fov = 60 }")).unwrap_err();
+        assert_eq!(message, "--view-height needs the orthographic projection, from --projection or the scene");
+        assert!(settings("--projection perspective --view-height 2").apply(&mut orthographic).is_err());
+    }
+}
@@ -0,0 +1,342 @@
+// Just enough of zlib (RFC 1950) and deflate (RFC 1951) for the image writers:
+// greedy LZ77 over a 32K window, emitted as a single block with the fixed Huffman codes.
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+const WINDOW: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 64;  // candidates tried per position; speed over ratio
+const HASH_BITS: u32 = 15;
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u32
+}
+
+impl BitWriter {
+    fn write(&mut self, bits: u32, count: u32) {  // least significant bit first
+        self.buffer |= bits << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+    fn write_code(&mut self, code: u32, length: u32) {  // Huffman codes go most significant bit first
+        self.write(code.reverse_bits() >> (32 - length), length);
+    }
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+fn write_literal(out: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => out.write_code(0x30 + symbol, 8),
+        144..=255 => out.write_code(0x190 + symbol - 144, 9),
+        256..=279 => out.write_code(symbol - 256, 7),
+        _ => out.write_code(0xC0 + symbol - 280, 8)
+    }
+}
+
+fn write_match(out: &mut BitWriter, length: usize, distance: usize) {
+    let code = LENGTH_BASE.iter().rposition(|&base| base as usize <= length).unwrap();
+    write_literal(out, 257 + code as u32);
+    out.write((length - LENGTH_BASE[code] as usize) as u32, LENGTH_EXTRA[code] as u32);
+    let code = DISTANCE_BASE.iter().rposition(|&base| base as usize <= distance).unwrap();
+    out.write_code(code as u32, 5);
+    out.write((distance - DISTANCE_BASE[code] as usize) as u32, DISTANCE_EXTRA[code] as u32);
+}
+
+fn hash(data: &[u8], i: usize) -> usize {
+    let v = (data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32;
+    (v.wrapping_mul(0x9E3779B1) >> (32 - HASH_BITS)) as usize
+}
+
+/// Compresses data into a raw deflate stream.
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut out = BitWriter {bytes: Vec::with_capacity(data.len() / 2), buffer: 0, count: 0};
+    out.write(1, 1);  // BFINAL
+    out.write(1, 2);  // BTYPE = fixed Huffman
+
+    // hash chains: head[hash] is the latest position with that hash, previous[pos % WINDOW] the one before
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut previous = vec![usize::MAX; WINDOW];
+    let insert = |head: &mut [usize], previous: &mut [usize], i: usize| {
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(data, i);
+            previous[i % WINDOW] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut best = (0, 0);  // (length, distance)
+        if i + MIN_MATCH <= data.len() {
+            let mut candidate = head[hash(data, i)];
+            let limit = (data.len() - i).min(MAX_MATCH);
+            for _ in 0..MAX_CHAIN {
+                if candidate == usize::MAX || i - candidate > WINDOW - 1 {
+                    break;
+                }
+                let length = (0..limit).take_while(|&k| data[candidate + k] == data[i + k]).count();
+                if length > best.0 {
+                    best = (length, i - candidate);
+                    if length == limit {
+                        break;
+                    }
+                }
+                let next = previous[candidate % WINDOW];
+                if next == usize::MAX || next >= candidate {
+                    break;  // the slot was reused by a newer position
+                }
+                candidate = next;
+            }
+        }
+        if best.0 >= MIN_MATCH {
+            write_match(&mut out, best.0, best.1);
+            for k in i..i + best.0 {
+                insert(&mut head, &mut previous, k);
+            }
+            i += best.0;
+        } else {
+            write_literal(&mut out, data[i] as u32);
+            insert(&mut head, &mut previous, i);
+            i += 1;
+        }
+    }
+    write_literal(&mut out, 256);  // end of block
+    out.finish()
+}
+
+/// Adler-32 checksum of zlib streams.
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {  // largest run before b can overflow
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+/// CRC-32 as used by PNG and zip.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Compresses data into a zlib stream: header, deflate data, adler32 of the uncompressed data.
+pub fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];  // 32K window, no dictionary, fastest compression level
+    out.extend(deflate(data));
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+// canonical Huffman code given by its code lengths, decoded a bit at a time (as in zlib's puff.c)
+struct Huffman {
+    counts: [u16; 16],  // number of codes of each length
+    symbols: Vec<u16>   // symbols ordered by code
+}
+
+impl Huffman {
+    fn construct(lengths: &[u8]) -> Huffman {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Huffman {counts, symbols}
+    }
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize  // in bits
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, count: u32) -> Result<u32, String> {
+        let mut value = 0;
+        for k in 0..count {
+            let byte = *self.bytes.get(self.position / 8).ok_or("unexpected end of deflate data")?;
+            value |= ((byte >> (self.position % 8)) as u32 & 1) << k;
+            self.position += 1;
+        }
+        Ok(value)
+    }
+    fn decode(&mut self, huffman: &Huffman) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..16 {
+            code |= self.bits(1)? as i32;
+            let count = huffman.counts[length] as i32;
+            if code - count < first {
+                return Ok(huffman.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid Huffman code".to_string())
+    }
+}
+
+/// Decompresses a raw deflate stream, with stored, fixed and dynamic Huffman blocks.
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    const ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+    let mut input = BitReader {bytes: data, position: 0};
+    let mut out: Vec<u8> = Vec::new();
+
+    loop {
+        let last = input.bits(1)? == 1;
+        match input.bits(2)? {
+            0 => {
+                let start = input.position.div_ceil(8);
+                let header = data.get(start..start + 4).ok_or("unexpected end of deflate data")?;
+                let length = u16::from_le_bytes([header[0], header[1]]) as usize;
+                if length != !u16::from_le_bytes([header[2], header[3]]) as usize {
+                    return Err("stored block length mismatch".to_string());
+                }
+                out.extend(data.get(start + 4..start + 4 + length).ok_or("unexpected end of deflate data")?);
+                input.position = (start + 4 + length) * 8;
+            },
+            kind @ (1 | 2) => {
+                let (literals, distances) = if kind == 1 {
+                    let mut lengths = [8u8; 288];
+                    lengths[144..256].fill(9);
+                    lengths[256..280].fill(7);
+                    (Huffman::construct(&lengths), Huffman::construct(&[5; 30]))
+                } else {
+                    let literal_count = input.bits(5)? as usize + 257;
+                    let distance_count = input.bits(5)? as usize + 1;
+                    let code_count = input.bits(4)? as usize + 4;
+                    let mut code_lengths = [0u8; 19];
+                    for &k in &ORDER[..code_count] {
+                        code_lengths[k] = input.bits(3)? as u8;
+                    }
+                    let code = Huffman::construct(&code_lengths);
+                    let mut lengths: Vec<u8> = Vec::with_capacity(literal_count + distance_count);
+                    while lengths.len() < literal_count + distance_count {
+                        let (value, repeat) = match input.decode(&code)? {
+                            symbol @ 0..=15 => (symbol as u8, 1),
+                            16 => (*lengths.last().ok_or("repeat with no previous length")?, 3 + input.bits(2)?),
+                            17 => (0, 3 + input.bits(3)?),
+                            _ => (0, 11 + input.bits(7)?)
+                        };
+                        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+                    }
+                    if lengths.len() > literal_count + distance_count {
+                        return Err("code lengths overrun".to_string());
+                    }
+                    (Huffman::construct(&lengths[..literal_count]), Huffman::construct(&lengths[literal_count..]))
+                };
+                loop {
+                    let symbol = input.decode(&literals)? as usize;
+                    if symbol < 256 {
+                        out.push(symbol as u8);
+                        continue;
+                    } else if symbol == 256 {
+                        break;
+                    }
+                    let code = symbol - 257;
+                    if code >= 29 {
+                        return Err("invalid length code".to_string());
+                    }
+                    let length = LENGTH_BASE[code] as usize + input.bits(LENGTH_EXTRA[code] as u32)? as usize;
+                    let code = input.decode(&distances)? as usize;
+                    if code >= 30 {
+                        return Err("invalid distance code".to_string());
+                    }
+                    let distance = DISTANCE_BASE[code] as usize + input.bits(DISTANCE_EXTRA[code] as u32)? as usize;
+                    if distance > out.len() {
+                        return Err("distance too far back".to_string());
+                    }
+                    for _ in 0..length {
+                        out.push(out[out.len() - distance]);
+                    }
+                }
+            },
+            _ => return Err("invalid block type".to_string())
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+pub fn zlib_decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < 6 || data[0] & 0x0F != 8 || !(data[0] as u16 * 256 + data[1] as u16).is_multiple_of(31) {
+        return Err("invalid zlib header".to_string());
+    }
+    if data[1] & 0x20 != 0 {
+        return Err("zlib preset dictionaries are not supported".to_string());
+    }
+    let out = inflate(&data[2..])?;
+    let checksum = u32::from_be_bytes(data[data.len() - 4..].try_into().unwrap());
+    if adler32(&out) != checksum {
+        return Err("zlib checksum mismatch".to_string());
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::RngCore;
+    use crate::random::Pcg32;
+
+    #[test]
+    fn deflate_round_trip() {
+        let mut random = vec![0u8; 50000];
+        Pcg32::construct(3, 0).fill_bytes(&mut random);
+        let repetitive: Vec<u8> = b"abc".iter().cycle().take(100000).copied().collect();
+        // repeats further back than the window reaches, then within it
+        let repeated = [&random[..40000], &random[..40000], &random[..1000]].concat();
+        for data in [&random[..], &repetitive, &repeated, &[], &[7]] {
+            assert_eq!(inflate(&deflate(data)).unwrap(), data);
+        }
+        assert!(deflate(&repetitive).len() < 1000);
+    }
+
+    #[test]
+    fn zlib_round_trip() {
+        let data: Vec<u8> = (0..10000u32).map(|i| (i * i / 7 % 251) as u8).collect();
+        let compressed = zlib_compress(&data);
+        assert_eq!(zlib_decompress(&compressed).unwrap(), data);
+
+        let mut corrupted = compressed.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert!(zlib_decompress(&corrupted).is_err());
+    }
+}
@@ -0,0 +1,191 @@
+use std::f64::consts::PI;
+use std::io;
+use std::path::Path;
+use crate::utils::{Point3, Color3, UNIT_Z};
+use crate::image::Framebuffer;
+use crate::background::Background;
+use crate::error::RaytraceError;
+
+// piecewise constant density over [0, 1), one piece per value
+struct Distribution1D {
+    values: Vec<f64>,
+    cdf: Vec<f64>,  // values.len() + 1 entries, from 0 to 1
+    integral: f64
+}
+
+impl Distribution1D {
+    fn construct(values: Vec<f64>) -> Distribution1D {
+        let n = values.len() as f64;
+        let mut cdf = vec![0.0];
+        for value in &values {
+            cdf.push(cdf.last().unwrap() + value / n);
+        }
+        let integral = *cdf.last().unwrap();
+        if integral > 0.0 {
+            cdf.iter_mut().for_each(|c| *c /= integral);
+        } else {
+            // nothing to prefer, uniform
+            cdf.iter_mut().enumerate().for_each(|(i, c)| *c = i as f64 / n);
+        }
+        Distribution1D {values, cdf, integral}
+    }
+    fn pdf(&self, i: usize) -> f64 {
+        if self.integral > 0.0 { self.values[i] / self.integral } else { 1.0 }
+    }
+    // a point in [0, 1) with u uniform in [0, 1), and the piece it is in
+    fn sample(&self, u: f64) -> (f64, usize) {
+        let i = (self.cdf.partition_point(|&c| c <= u) - 1).min(self.values.len() - 1);
+        let width = self.cdf[i + 1] - self.cdf[i];
+        let offset = if width > 0.0 { (u - self.cdf[i]) / width } else { 0.5 };
+        (((i as f64 + offset.clamp(0.0, 1.0)) / self.values.len() as f64).min(1.0 - f64::EPSILON), i)
+    }
+}
+
+// piecewise constant density over [0, 1)^2: a row by the marginal density, then a column in it
+struct Distribution2D {
+    rows: Vec<Distribution1D>,
+    marginal: Distribution1D
+}
+
+impl Distribution2D {
+    fn construct(values: &[f64], width: usize) -> Distribution2D {
+        let rows: Vec<Distribution1D> = values.chunks(width).map(|row| Distribution1D::construct(row.to_vec())).collect();
+        let marginal = Distribution1D::construct(rows.iter().map(|row| row.integral).collect());
+        Distribution2D {rows, marginal}
+    }
+    // (x, y) with u uniform in [0, 1)^2
+    fn sample(&self, u: (f64, f64)) -> (f64, f64) {
+        let (y, row) = self.marginal.sample(u.1);
+        let (x, _) = self.rows[row].sample(u.0);
+        (x, y)
+    }
+    fn pdf(&self, column: usize, row: usize) -> f64 {
+        self.marginal.pdf(row) * self.rows[row].pdf(column)
+    }
+}
+
+/// Light from all directions around the scene, from an equirectangular image: longitude across
+/// its width and latitude up its height, laid out as the equirectangular camera projection
+/// renders from the default view (+Y in the middle, +X to the right of it, +Z up). Directions
+/// are sampled in proportion to the brightness of the image, so small bright spots like the
+/// sun are found by light sampling.
+pub struct EnvironmentMap {
+    image: Framebuffer,
+    pub rotation: f64,   // degrees counterclockwise about +Z
+    pub intensity: f64,  // scales the image
+    distribution: Distribution2D
+}
+
+impl EnvironmentMap {
+    pub fn construct(image: Framebuffer) -> Result<EnvironmentMap, RaytraceError> {
+        if image.width == 0 || image.height == 0 {
+            return Err(RaytraceError::EmptyImage);
+        }
+        // luminance, times the solid angle each pixel covers (rows shrink towards the poles)
+        let weights: Vec<f64> = image.pixels.iter().enumerate().map(|(i, c)| {
+            let latitude = (0.5 - ((i / image.width) as f64 + 0.5) / image.height as f64) * PI;
+            let luminance = 0.2126*c.r + 0.7152*c.g + 0.0722*c.b;
+            luminance.max(0.0) * latitude.cos()
+        }).collect();
+        let distribution = Distribution2D::construct(&weights, image.width);
+        Ok(EnvironmentMap {image, rotation: 0.0, intensity: 1.0, distribution})
+    }
+    /// Reads a .hdr, .pfm or .exr image.
+    pub fn load(path: &Path) -> io::Result<EnvironmentMap> {
+        Self::construct(Framebuffer::read(path)?).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+    // the pixel a unit direction falls in
+    fn pixel(&self, direction: Point3) -> (usize, usize) {
+        let d = direction.rotate(UNIT_Z, -self.rotation.to_radians());
+        let longitude = d.x.atan2(d.y);
+        let latitude = d.z.clamp(-1.0, 1.0).asin();
+        let x = ((longitude / PI + 1.0) / 2.0 * self.image.width as f64) as usize;
+        let y = ((0.5 - latitude / PI) * self.image.height as f64) as usize;
+        (x.min(self.image.width - 1), y.min(self.image.height - 1))
+    }
+}
+
+impl Background for EnvironmentMap {
+    fn radiance(&self, direction: Point3) -> Color3 {
+        let (x, y) = self.pixel(direction);
+        self.image.get(x, y) * self.intensity
+    }
+    fn is_emitter(&self) -> bool {
+        true
+    }
+    fn sample(&self, u: (f64, f64)) -> Option<Point3> {
+        let (x, y) = self.distribution.sample(u);
+        let longitude = (2.0*x - 1.0) * PI;
+        let latitude = (0.5 - y) * PI;
+        let d = Point3 {x: latitude.cos()*longitude.sin(), y: latitude.cos()*longitude.cos(), z: latitude.sin()};
+        Some(d.rotate(UNIT_Z, self.rotation.to_radians()))
+    }
+    fn pdf(&self, direction: Point3) -> f64 {
+        let (x, y) = self.pixel(direction);
+        let cos_latitude = (1.0 - direction.z*direction.z).max(0.0).sqrt();
+        if cos_latitude == 0.0 {
+            return 0.0;
+        }
+        // the image covers 2 pi of longitude by pi of latitude
+        self.distribution.pdf(x, y) / (2.0 * PI * PI * cos_latitude)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+    use crate::random::Pcg32;
+
+    // a dim map with a bright spot and a dark band, turned away from the image's layout
+    fn uneven_map() -> EnvironmentMap {
+        let mut image = Framebuffer::construct(16, 8);
+        for y in 0..8 {
+            for x in 0..16 {
+                let value = if (x, y) == (11, 2) { 50.0 } else if y == 5 { 0.0 } else { 0.2 + 0.1 * x as f64 };
+                image.set(x, y, Color3 {r: value, g: value * 0.5, b: value * 0.25});
+            }
+        }
+        let mut map = EnvironmentMap::construct(image).unwrap();
+        map.rotation = 40.0;
+        map
+    }
+
+    #[test]
+    fn sampled_directions_have_the_pdf_of_their_pixel() {
+        let map = uneven_map();
+        let mut rng = Pcg32::construct(3, 0);
+        for _ in 0..1000 {
+            let u = (rng.random(), rng.random());
+            let direction = map.sample(u).unwrap();
+            assert!((direction.norm() - 1.0).abs() < 1e-12);
+            // the pixel the distribution picked, whatever direction that became after rotating
+            let (x, y) = map.distribution.sample(u);
+            let (column, row) = ((x * 16.0) as usize, (y * 8.0) as usize);
+            assert_eq!(map.pixel(direction), (column, row));
+            let cos_latitude = (1.0 - direction.z*direction.z).sqrt();
+            let expected = map.distribution.pdf(column, row) / (2.0 * PI * PI * cos_latitude);
+            assert!((map.pdf(direction) - expected).abs() <= 1e-9 * expected, "{} vs {}", map.pdf(direction), expected);
+            assert!(map.pdf(direction) > 0.0);
+        }
+    }
+
+    #[test]
+    fn pdf_integrates_to_one_over_the_sphere() {
+        let map = uneven_map();
+        // half degree steps of longitude and 1/80 of the latitude put every pixel's edges, turned
+        // by 40 degrees, between steps, so the midpoints sum up the piecewise constant pdf exactly
+        let (steps_latitude, steps_longitude) = (80, 720);
+        let mut total = 0.0;
+        for i in 0..steps_latitude {
+            let latitude = ((i as f64 + 0.5) / steps_latitude as f64 - 0.5) * PI;
+            for j in 0..steps_longitude {
+                let longitude = (j as f64 + 0.5) / steps_longitude as f64 * 2.0 * PI;
+                let direction = Point3 {x: latitude.cos() * longitude.sin(), y: latitude.cos() * longitude.cos(), z: latitude.sin()};
+                total += map.pdf(direction) * latitude.cos();
+            }
+        }
+        let integral = total * PI / steps_latitude as f64 * 2.0 * PI / steps_longitude as f64;
+        assert!((integral - 1.0).abs() < 1e-9, "{}", integral);
+    }
+}
@@ -0,0 +1,71 @@
+use std::fmt;
+
+/// Invalid input to a constructor of the scene types.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RaytraceError {
+    InvalidColor {r: f64, g: f64, b: f64},  // a component is negative, infinite or NaN
+    InvalidAlbedo {r: f64, g: f64, b: f64},  // a component is outside [0, 1]
+    InvalidFuzz(f64),
+    InvalidRefractiveIndex(f64),
+    InvalidIntensity(f64),
+    ZeroNormal,  // a plane or triangle whose normal has no direction
+    ZeroAxis,    // a rotation about (0, 0, 0)
+    ZeroDirection,  // a spot or directional light pointing nowhere
+    InvalidConeAngle(f64),
+    InvalidFalloff(f64),
+    InvalidAngularDiameter(f64),
+    InvalidFieldOfView(f64),
+    DegenerateView,  // the camera looks at itself, or straight along its up vector
+    InvalidAperture(f64),
+    InvalidFocusDistance(f64),
+    InvalidViewHeight(f64),
+    InvalidShutter(f64, f64),
+    EmptyImage,  // an environment map without pixels
+    InvalidCheckerSize(f64),
+    InvalidSunElevation(f64),
+    InvalidTurbidity(f64),
+    InvalidRadius(f64),
+    MeshIndexOutOfRange {index: usize, vertices: usize},
+    MeshAttributeLength {attribute: &'static str, expected: usize, found: usize}
+}
+
+impl fmt::Display for RaytraceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RaytraceError::InvalidColor {r, g, b} =>
+                write!(f, "invalid color ({}, {}, {}): components must be finite and not negative", r, g, b),
+            RaytraceError::InvalidAlbedo {r, g, b} =>
+                write!(f, "invalid albedo ({}, {}, {}): components must be between 0 and 1", r, g, b),
+            RaytraceError::InvalidFuzz(fuzz) => write!(f, "invalid fuzz {}: must be between 0 and 1", fuzz),
+            RaytraceError::InvalidRefractiveIndex(index) =>
+                write!(f, "invalid refractive index {}: must be finite and positive", index),
+            RaytraceError::InvalidIntensity(intensity) => write!(f, "invalid light intensity {}: must be finite and not negative", intensity),
+            RaytraceError::ZeroNormal => write!(f, "the normal is zero: points are collinear or the normal vector is (0, 0, 0)"),
+            RaytraceError::ZeroAxis => write!(f, "the rotation axis is zero"),
+            RaytraceError::ZeroDirection => write!(f, "the light direction is zero"),
+            RaytraceError::InvalidConeAngle(angle) => write!(f, "invalid spot light angle {}: must be between 0 and 180 degrees", angle),
+            RaytraceError::InvalidFalloff(falloff) =>
+                write!(f, "invalid spot light falloff {}: must be between 0 degrees and the cone angle", falloff),
+            RaytraceError::InvalidAngularDiameter(diameter) =>
+                write!(f, "invalid angular diameter {}: must be at least 0 and below 180 degrees", diameter),
+            RaytraceError::InvalidFieldOfView(fov) => write!(f, "invalid field of view {}: must be between 0 and 180 degrees, or up to 360 for fisheye", fov),
+            RaytraceError::DegenerateView => write!(f, "the camera looks at its own position, or along its up vector"),
+            RaytraceError::InvalidAperture(aperture) =>
+                write!(f, "invalid aperture {}: the radius must not be negative and the f-stop must be positive", aperture),
+            RaytraceError::InvalidFocusDistance(distance) => write!(f, "invalid focus distance {}: must be positive", distance),
+            RaytraceError::InvalidViewHeight(height) => write!(f, "invalid orthographic view height {}: must be positive", height),
+            RaytraceError::InvalidShutter(open, close) => write!(f, "invalid shutter interval {} to {}: must not close before it opens", open, close),
+            RaytraceError::EmptyImage => write!(f, "the environment image has no pixels"),
+            RaytraceError::InvalidCheckerSize(size) => write!(f, "invalid checker size {}: must be between 0 and 180 degrees", size),
+            RaytraceError::InvalidSunElevation(elevation) => write!(f, "invalid sun elevation {}: must be between 0 and 90 degrees", elevation),
+            RaytraceError::InvalidTurbidity(turbidity) => write!(f, "invalid turbidity {}: must be between 1.7 and 10", turbidity),
+            RaytraceError::InvalidRadius(radius) => write!(f, "invalid radius {}: must be finite and positive", radius),
+            RaytraceError::MeshIndexOutOfRange {index, vertices} =>
+                write!(f, "mesh index {} out of range for {} vertices", index, vertices),
+            RaytraceError::MeshAttributeLength {attribute, expected, found} =>
+                write!(f, "mesh has {} {} for {} vertices, needs one per vertex", found, attribute, expected)
+        }
+    }
+}
+
+impl std::error::Error for RaytraceError {}
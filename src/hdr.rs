@@ -0,0 +1,625 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::utils::{Color3, BLACK};
+use crate::image::{Framebuffer, ImageFormat};
+use crate::deflate::{zlib_compress, zlib_decompress};
+
+// Floating point image formats, which keep the radiance unclamped:
+// Portable Float Map, Radiance RGBE (.hdr) and a scanline subset of OpenEXR.
+
+/// Pixel data compression for [`Framebuffer::encode_exr`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ExrCompression {
+    None,
+    Zip  // zlib over blocks of 16 scanlines
+}
+
+impl ExrCompression {
+    fn code(self) -> u8 {
+        match self {
+            ExrCompression::None => 0,
+            ExrCompression::Zip => 3
+        }
+    }
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+// the next whitespace delimited token of an ASCII header, and the offset just past it
+fn token(bytes: &[u8], start: usize) -> io::Result<(&str, usize)> {
+    let begin = start + bytes[start.min(bytes.len())..].iter().take_while(|b| b.is_ascii_whitespace()).count();
+    let end = begin + bytes[begin.min(bytes.len())..].iter().take_while(|b| !b.is_ascii_whitespace()).count();
+    if begin == end {
+        return Err(invalid("truncated header"));
+    }
+    let text = std::str::from_utf8(&bytes[begin..end]).map_err(|_| invalid("header is not ASCII"))?;
+    Ok((text, end))
+}
+
+fn number<T: std::str::FromStr>(text: &str) -> io::Result<T> {
+    text.parse().map_err(|_| invalid(format!("invalid number '{}' in header", text)))
+}
+
+// Radiance shared exponent encoding, as in Greg Ward's rgbe.c
+fn to_rgbe(color: Color3) -> [u8; 4] {
+    let v = color.r.max(color.g).max(color.b);
+    if v.is_nan() || v < 1e-32 {
+        return [0; 4];
+    }
+    let mut exponent = v.log2().floor() as i32 + 1;  // v = mantissa * 2^exponent, mantissa in [0.5, 1)
+    let mut mantissa = v / 2f64.powi(exponent);
+    if mantissa >= 1.0 {
+        exponent += 1;
+        mantissa /= 2.0;
+    } else if mantissa < 0.5 {
+        exponent -= 1;
+        mantissa *= 2.0;
+    }
+    if exponent > 127 {
+        return [255, 255, 255, 255];
+    }
+    let scale = mantissa * 256.0 / v;
+    let byte = |c: f64| (c.max(0.0) * scale).min(255.0) as u8;
+    [byte(color.r), byte(color.g), byte(color.b), (exponent + 128) as u8]
+}
+
+fn from_rgbe(rgbe: [u8; 4]) -> Color3 {
+    if rgbe[3] == 0 {
+        return BLACK;
+    }
+    let f = 2f64.powi(rgbe[3] as i32 - (128 + 8));
+    Color3 {r: (rgbe[0] as f64 + 0.5) * f, g: (rgbe[1] as f64 + 0.5) * f, b: (rgbe[2] as f64 + 0.5) * f}
+}
+
+fn half_to_f32(h: u16) -> f32 {
+    let sign = if h & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((h >> 10) & 0x1F) as i32;
+    let mantissa = (h & 0x3FF) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        31 if mantissa == 0.0 => f32::INFINITY,
+        31 => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15)
+    }
+}
+
+// EXR's ZIP predictor: split even and odd bytes into two halves, then delta encode
+fn exr_zip_pack(raw: &[u8]) -> Vec<u8> {
+    let half = raw.len().div_ceil(2);
+    let mut packed = vec![0u8; raw.len()];
+    for (i, &byte) in raw.iter().enumerate() {
+        packed[if i % 2 == 0 { i / 2 } else { half + i / 2 }] = byte;
+    }
+    for i in (1..packed.len()).rev() {
+        packed[i] = packed[i].wrapping_sub(packed[i - 1]).wrapping_add(128);
+    }
+    packed
+}
+
+fn exr_zip_unpack(mut packed: Vec<u8>) -> Vec<u8> {
+    for i in 1..packed.len() {
+        packed[i] = packed[i - 1].wrapping_add(packed[i]).wrapping_sub(128);
+    }
+    let half = packed.len().div_ceil(2);
+    (0..packed.len()).map(|i| packed[if i % 2 == 0 { i / 2 } else { half + i / 2 }]).collect()
+}
+
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    position: usize
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, count: usize) -> io::Result<&'a [u8]> {
+        let taken = self.bytes.get(self.position..self.position + count).ok_or_else(|| invalid("truncated file"))?;
+        self.position += count;
+        Ok(taken)
+    }
+    fn string(&mut self) -> io::Result<String> {  // null terminated
+        let length = self.bytes[self.position.min(self.bytes.len())..].iter().position(|&b| b == 0)
+            .ok_or_else(|| invalid("unterminated string"))?;
+        let text = String::from_utf8_lossy(self.take(length)?).into_owned();
+        self.position += 1;
+        Ok(text)
+    }
+    fn line(&mut self) -> io::Result<&'a [u8]> {  // newline terminated
+        let length = self.bytes[self.position.min(self.bytes.len())..].iter().position(|&b| b == b'\n')
+            .ok_or_else(|| invalid("unterminated header line"))?;
+        let line = self.take(length)?;
+        self.position += 1;
+        Ok(line)
+    }
+}
+
+struct ExrChannel {
+    name: String,
+    pixel_type: i32  // 0 UINT, 1 HALF, 2 FLOAT
+}
+
+impl ExrChannel {
+    fn size(&self) -> usize {
+        if self.pixel_type == 1 { 2 } else { 4 }
+    }
+    fn value(&self, bytes: &[u8]) -> f64 {
+        match self.pixel_type {
+            0 => u32::from_le_bytes(bytes[..4].try_into().unwrap()) as f64,
+            1 => half_to_f32(u16::from_le_bytes(bytes[..2].try_into().unwrap())) as f64,
+            _ => f32::from_le_bytes(bytes[..4].try_into().unwrap()) as f64
+        }
+    }
+}
+
+impl Framebuffer {
+    /// Reads a floating point image, in the format given by the file extension.
+    pub fn read(path: &Path) -> io::Result<Framebuffer> {
+        let bytes = fs::read(path)?;
+        match ImageFormat::from_path(path) {
+            Some(ImageFormat::Pfm) => Self::decode_pfm(&bytes),
+            Some(ImageFormat::Hdr) => Self::decode_hdr(&bytes),
+            Some(ImageFormat::Exr) => Self::decode_exr(&bytes),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("cannot read {}, only .pfm, .hdr and .exr are supported", path.display())))
+        }
+    }
+
+    pub fn encode_pfm(&self) -> Vec<u8> {
+        // little endian (negative scale), rows bottom up
+        let mut out = format!("PF\n{} {}\n-1.0\n", self.width, self.height).into_bytes();
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                let c = self.get(x, y);
+                for v in [c.r, c.g, c.b] {
+                    out.extend((v as f32).to_le_bytes());
+                }
+            }
+        }
+        out
+    }
+    pub fn decode_pfm(bytes: &[u8]) -> io::Result<Framebuffer> {
+        let (magic, end) = token(bytes, 0)?;
+        let channels = match magic {
+            "PF" => 3,
+            "Pf" => 1,
+            _ => return Err(invalid("not a PFM file"))
+        };
+        let (width, end) = token(bytes, end)?;
+        let (height, end) = token(bytes, end)?;
+        let (scale, end) = token(bytes, end)?;
+        let (width, height, scale): (usize, usize, f64) = (number(width)?, number(height)?, number(scale)?);
+        // a single whitespace character ends the header
+        let data = bytes.get(end + 1..).ok_or_else(|| invalid("truncated PFM header"))?;
+        let size = width.checked_mul(height).and_then(|n| n.checked_mul(channels * 4))
+            .ok_or_else(|| invalid("PFM image too large"))?;
+        if data.len() < size {
+            return Err(invalid("truncated PFM pixel data"));
+        }
+        let float = |i: usize| {
+            let raw: [u8; 4] = data[i * 4..i * 4 + 4].try_into().unwrap();
+            (if scale < 0.0 { f32::from_le_bytes(raw) } else { f32::from_be_bytes(raw) }) as f64
+        };
+        let mut image = Framebuffer::construct(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let i = ((height - 1 - y) * width + x) * channels;
+                let c = if channels == 3 {
+                    Color3 {r: float(i), g: float(i + 1), b: float(i + 2)}
+                } else {
+                    Color3 {r: float(i), g: float(i), b: float(i)}
+                };
+                image.set(x, y, c);
+            }
+        }
+        Ok(image)
+    }
+
+    pub fn encode_hdr(&self) -> Vec<u8> {
+        let mut out = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", self.height, self.width).into_bytes();
+        for y in 0..self.height {
+            let line: Vec<[u8; 4]> = (0..self.width).map(|x| to_rgbe(self.get(x, y))).collect();
+            if !(8..32768).contains(&self.width) {
+                out.extend(line.iter().flatten());  // too short or long for run length encoding
+                continue;
+            }
+            out.extend([2, 2, (self.width >> 8) as u8, self.width as u8]);
+            for channel in 0..4 {
+                // runs of 3 or more equal bytes become (128 + count, byte), the rest (count, bytes...)
+                let values: Vec<u8> = line.iter().map(|p| p[channel]).collect();
+                let mut i = 0;
+                while i < values.len() {
+                    let run = values[i..].iter().take(127).take_while(|&&v| v == values[i]).count();
+                    if run >= 3 {
+                        out.extend([128 + run as u8, values[i]]);
+                        i += run;
+                        continue;
+                    }
+                    let mut end = i;
+                    while end < values.len() && end - i < 128
+                        && !(end + 2 < values.len() && values[end] == values[end + 1] && values[end] == values[end + 2]) {
+                        end += 1;
+                    }
+                    out.push((end - i) as u8);
+                    out.extend(&values[i..end]);
+                    i = end;
+                }
+            }
+        }
+        out
+    }
+    pub fn decode_hdr(bytes: &[u8]) -> io::Result<Framebuffer> {
+        if !bytes.starts_with(b"#?") {
+            return Err(invalid("not a Radiance HDR file"));
+        }
+        let mut input = ByteReader {bytes, position: 0};
+        loop {
+            let line = input.line()?;
+            if line.is_empty() {
+                break;
+            }
+            if line.starts_with(b"FORMAT=") && line != b"FORMAT=32-bit_rle_rgbe" {
+                return Err(invalid(format!("unsupported HDR {}", String::from_utf8_lossy(line))));
+            }
+        }
+        let resolution = String::from_utf8_lossy(input.line()?).into_owned();
+        let parts: Vec<&str> = resolution.split_whitespace().collect();
+        let [minus_y, height, plus_x, width] = parts[..] else { return Err(invalid("invalid resolution line")) };
+        if minus_y != "-Y" || plus_x != "+X" {
+            return Err(invalid(format!("unsupported HDR orientation '{}'", resolution)));
+        }
+        let (width, height): (usize, usize) = (number(width)?, number(height)?);
+        // every scanline takes at least 4 bytes, and run length encoding packs at most 127
+        // pixels of a channel into 2 bytes, so no file packs more than 32 pixels into a byte
+        let remaining = bytes.len() - input.position;
+        let pixels = width.checked_mul(height).ok_or_else(|| invalid("HDR image too large"))?;
+        if width > 0 && (height > remaining / 4 || pixels / 32 > remaining) {
+            return Err(invalid(format!("{} by {} pixels do not fit in the rest of the file", width, height)));
+        }
+
+        let mut image = Framebuffer::construct(width, height);
+        let mut line = vec![[0u8; 4]; width];
+        for y in 0..if width > 0 { height } else { 0 } {
+            let start: [u8; 4] = input.take(4)?.try_into().unwrap();
+            if start[0] == 2 && start[1] == 2 && start[2] & 0x80 == 0 && (8..32768).contains(&width) {
+                if ((start[2] as usize) << 8 | start[3] as usize) != width {
+                    return Err(invalid("scanline width mismatch"));
+                }
+                for channel in 0..4 {
+                    let mut x = 0;
+                    while x < width {
+                        let count = input.take(1)?[0] as usize;
+                        if count > 128 {
+                            let value = input.take(1)?[0];
+                            for pixel in line.get_mut(x..x + count - 128).ok_or_else(|| invalid("run overflows scanline"))? {
+                                pixel[channel] = value;
+                            }
+                            x += count - 128;
+                        } else {
+                            if count == 0 || x + count > width {
+                                return Err(invalid("invalid run in scanline"));
+                            }
+                            for (pixel, &value) in line[x..x + count].iter_mut().zip(input.take(count)?) {
+                                pixel[channel] = value;
+                            }
+                            x += count;
+                        }
+                    }
+                }
+            } else {
+                // flat pixels, possibly with old style (1, 1, 1, count) repeats
+                let mut x = 0;
+                let mut pixel = start;
+                let mut shift = 0;
+                loop {
+                    if pixel[..3] == [1, 1, 1] && x > 0 {
+                        if shift >= usize::BITS {
+                            return Err(invalid("run length overflows"));
+                        }
+                        let count = ((pixel[3] as usize) << shift).min(width - x);
+                        let previous = line[x - 1];
+                        for p in line.get_mut(x..x + count).ok_or_else(|| invalid("run overflows scanline"))? {
+                            *p = previous;
+                        }
+                        x += count;
+                        shift += 8;
+                    } else {
+                        line[x] = pixel;
+                        x += 1;
+                        shift = 0;
+                    }
+                    if x >= width {
+                        break;
+                    }
+                    pixel = input.take(4)?.try_into().unwrap();
+                }
+            }
+            for (x, &rgbe) in line.iter().enumerate() {
+                image.set(x, y, from_rgbe(rgbe));
+            }
+        }
+        Ok(image)
+    }
+
+    pub fn encode_exr(&self, compression: ExrCompression) -> Vec<u8> {
+        fn attribute(out: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+            for text in [name, kind] {
+                out.extend(text.as_bytes());
+                out.push(0);
+            }
+            out.extend((value.len() as i32).to_le_bytes());
+            out.extend(value);
+        }
+        let (w, h) = (self.width as i32, self.height as i32);
+        let window: Vec<u8> = [0, 0, w - 1, h - 1].iter().flat_map(|v| v.to_le_bytes()).collect();
+        let mut channels = Vec::new();
+        for name in ["B", "G", "R"] {  // channels are listed alphabetically
+            channels.extend(name.as_bytes());
+            channels.extend([0]);
+            channels.extend(2i32.to_le_bytes());  // FLOAT
+            channels.extend([0, 0, 0, 0]);        // pLinear, reserved
+            channels.extend(1i32.to_le_bytes());  // x and y sampling
+            channels.extend(1i32.to_le_bytes());
+        }
+        channels.push(0);
+
+        let mut out = vec![0x76, 0x2F, 0x31, 0x01, 2, 0, 0, 0];  // magic, version 2 single part scanline
+        attribute(&mut out, "channels", "chlist", &channels);
+        attribute(&mut out, "compression", "compression", &[compression.code()]);
+        attribute(&mut out, "dataWindow", "box2i", &window);
+        attribute(&mut out, "displayWindow", "box2i", &window);
+        attribute(&mut out, "lineOrder", "lineOrder", &[0]);  // increasing y
+        attribute(&mut out, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+        attribute(&mut out, "screenWindowCenter", "v2f", &[0; 8]);
+        attribute(&mut out, "screenWindowWidth", "float", &1f32.to_le_bytes());
+        out.push(0);
+
+        let lines = if compression == ExrCompression::Zip { 16 } else { 1 };
+        let blocks = self.height.div_ceil(lines);
+        let table = out.len();
+        out.resize(table + blocks * 8, 0);
+        for block in 0..blocks {
+            let offset = out.len() as u64;
+            out[table + block * 8..table + block * 8 + 8].copy_from_slice(&offset.to_le_bytes());
+            let mut raw = Vec::with_capacity(lines * self.width * 12);
+            for y in block * lines..((block + 1) * lines).min(self.height) {
+                for channel in [|c: Color3| c.b, |c: Color3| c.g, |c: Color3| c.r] {
+                    for x in 0..self.width {
+                        raw.extend((channel(self.get(x, y)) as f32).to_le_bytes());
+                    }
+                }
+            }
+            let data = match compression {
+                ExrCompression::Zip => {
+                    let compressed = zlib_compress(&exr_zip_pack(&raw));
+                    if compressed.len() < raw.len() { compressed } else { raw }  // stored raw when it doesn't help
+                },
+                ExrCompression::None => raw
+            };
+            out.extend(((block * lines) as i32).to_le_bytes());
+            out.extend((data.len() as i32).to_le_bytes());
+            out.extend(data);
+        }
+        out
+    }
+    pub fn decode_exr(bytes: &[u8]) -> io::Result<Framebuffer> {
+        let int = |b: &[u8]| i32::from_le_bytes(b[..4].try_into().unwrap());
+        let mut input = ByteReader {bytes, position: 0};
+        if input.take(4)? != [0x76, 0x2F, 0x31, 0x01] {
+            return Err(invalid("not an OpenEXR file"));
+        }
+        if int(input.take(4)?) & 0x1200 != 0 {
+            return Err(invalid("tiled and multi-part OpenEXR files are not supported"));
+        }
+
+        let mut channels: Vec<ExrChannel> = Vec::new();
+        let mut compression = None;
+        let mut window = None;
+        loop {
+            let name = input.string()?;
+            if name.is_empty() {
+                break;
+            }
+            input.string()?;  // attribute type
+            let size = int(input.take(4)?);
+            let value = input.take(size.max(0) as usize)?;
+            match name.as_str() {
+                "channels" => {
+                    let mut rest = value;
+                    while let Some(end) = rest.iter().position(|&b| b == 0) {
+                        if end == 0 {
+                            break;
+                        }
+                        let name = String::from_utf8_lossy(&rest[..end]).into_owned();
+                        let pixel_type = int(rest.get(end + 1..end + 5).ok_or_else(|| invalid("truncated channel list"))?);
+                        if !(0..=2).contains(&pixel_type) {
+                            return Err(invalid(format!("invalid pixel type {} for channel {}", pixel_type, name)));
+                        }
+                        channels.push(ExrChannel {name, pixel_type});
+                        rest = rest.get(end + 17..).ok_or_else(|| invalid("truncated channel list"))?;
+                    }
+                },
+                "compression" => compression = value.first().copied(),
+                "dataWindow" if value.len() == 16 => window = Some([int(&value[0..]), int(&value[4..]), int(&value[8..]), int(&value[12..])]),
+                _ => {}
+            }
+        }
+        let lines = match compression {
+            Some(0) => 1,
+            Some(2) => 1,
+            Some(3) => 16,
+            Some(c) => return Err(invalid(format!("unsupported OpenEXR compression {}", c))),
+            None => return Err(invalid("missing compression attribute"))
+        };
+        let [x_min, y_min, x_max, y_max] = window.ok_or_else(|| invalid("missing dataWindow attribute"))?;
+        if channels.is_empty() {
+            return Err(invalid("no channels"));
+        }
+        if x_max < x_min {
+            return Err(invalid("empty dataWindow"));
+        }
+        let width = (x_max as i64 - x_min as i64 + 1) as usize;
+        let height = (y_max as i64 - y_min as i64 + 1).max(0) as usize;
+        let pixel_size: usize = channels.iter().map(|c| c.size()).sum();
+        let line_size = width.checked_mul(pixel_size).ok_or_else(|| invalid("OpenEXR image too large"))?;
+        // deflate packs at most 258 bytes into a match of 2 bits or more, about 1032 to 1
+        let ratio = if compression == Some(0) { 1 } else { 1032 };
+        let remaining = bytes.len() - input.position;
+        if line_size.checked_mul(height).is_none_or(|size| size / ratio > remaining) {
+            return Err(invalid(format!("{} by {} pixels do not fit in the rest of the file", width, height)));
+        }
+
+        let mut image = Framebuffer::construct(width, height);
+        for block in 0..height.div_ceil(lines) {
+            let offset = u64::from_le_bytes(input.take(8)?.try_into().unwrap()) as usize;
+            let start = offset.checked_add(8).ok_or_else(|| invalid("chunk offset out of range"))?;
+            let chunk = bytes.get(offset..start).ok_or_else(|| invalid("chunk offset out of range"))?;
+            let first = (int(chunk) as i64 - y_min as i64).max(0) as usize;
+            let size = int(&chunk[4..]).max(0) as usize;
+            let data = start.checked_add(size).and_then(|end| bytes.get(start..end)).ok_or_else(|| invalid("truncated chunk"))?;
+            let count = lines.min(height.saturating_sub(first));
+            let raw = if size == line_size * count {
+                data.to_vec()
+            } else {
+                exr_zip_unpack(zlib_decompress(data).map_err(invalid)?)
+            };
+            if raw.len() != line_size * count {
+                return Err(invalid(format!("chunk {} has the wrong size", block)));
+            }
+            for (row, line) in raw.chunks(line_size).enumerate() {
+                let mut start = 0;
+                for channel in &channels {
+                    for x in 0..width {
+                        let v = channel.value(&line[start + x * channel.size()..]);
+                        let y = first + row;
+                        let mut c = image.get(x, y);
+                        match channel.name.as_str() {
+                            "R" => c.r = v,
+                            "G" => c.g = v,
+                            "B" => c.b = v,
+                            "Y" => c = Color3 {r: v, g: v, b: v},
+                            _ => {}
+                        }
+                        image.set(x, y, c);
+                    }
+                    start += width * channel.size();
+                }
+            }
+        }
+        Ok(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+    use crate::random::Pcg32;
+
+    // random pixels over a wide range, with flat runs on every other row for run length encoding
+    fn test_image(width: usize, height: usize) -> Framebuffer {
+        let mut rng = Pcg32::construct(1, 0);
+        let mut image = Framebuffer::construct(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let mut value = || 10f64.powf(rng.random_range(-3.0..3.0));
+                let c = if y % 2 == 0 { Color3 {r: value(), g: value(), b: value()} } else { Color3 {r: 0.25, g: 0.5, b: 4.0} };
+                image.set(x, y, c);
+            }
+        }
+        image
+    }
+
+    fn assert_exact(image: &Framebuffer, decoded: &Framebuffer) {
+        assert_eq!((decoded.width, decoded.height), (image.width, image.height));
+        for (a, b) in image.pixels.iter().zip(&decoded.pixels) {
+            assert_eq!([a.r as f32, a.g as f32, a.b as f32], [b.r as f32, b.g as f32, b.b as f32]);
+        }
+    }
+
+    #[test]
+    fn pfm_round_trip() {
+        let image = test_image(7, 5);
+        assert_exact(&image, &Framebuffer::decode_pfm(&image.encode_pfm()).unwrap());
+
+        // grayscale, big endian (positive scale), rows bottom up
+        let mut bytes = b"Pf\n3 2\n1.0\n".to_vec();
+        for v in [4.0f32, 5.0, 6.0, 1.0, 2.0, 3.0] {
+            bytes.extend(v.to_be_bytes());
+        }
+        let gray = Framebuffer::decode_pfm(&bytes).unwrap();
+        assert_eq!((gray.width, gray.height), (3, 2));
+        for (c, v) in gray.pixels.iter().zip([1.0, 2.0, 3.0, 4.0, 5.0, 6.0]) {
+            assert_eq!((c.r, c.g, c.b), (v, v, v));
+        }
+    }
+
+    #[test]
+    fn pfm_rejects_truncated_files() {
+        let bytes = test_image(4, 4).encode_pfm();
+        assert!(Framebuffer::decode_pfm(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Framebuffer::decode_pfm(b"PF\n4 4\n-1.0").is_err());
+        assert!(Framebuffer::decode_pfm(b"PF\n99999999999 99999999999\n-1.0\n").is_err());
+    }
+
+    #[test]
+    fn hdr_round_trip() {
+        // 5 is too narrow for run length encoding, 40 is encoded with it
+        for width in [5, 40] {
+            let image = test_image(width, 6);
+            let encoded = image.encode_hdr();
+            let decoded = Framebuffer::decode_hdr(&encoded).unwrap();
+            assert_eq!((decoded.width, decoded.height), (width, 6));
+            for (a, b) in image.pixels.iter().zip(&decoded.pixels) {
+                // 8 bit mantissas, relative to the brightest component
+                let tolerance = a.r.max(a.g).max(a.b) / 128.0;
+                for (u, v) in [(a.r, b.r), (a.g, b.g), (a.b, b.b)] {
+                    assert!((u - v).abs() <= tolerance, "{} decoded as {}", u, v);
+                }
+            }
+            assert_eq!(decoded.encode_hdr(), encoded);
+        }
+    }
+
+    #[test]
+    fn hdr_rejects_crafted_files() {
+        let header = |width: usize, height: usize| format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", height, width).into_bytes();
+        // old style repeats of nothing, each shifting the count 8 bits further
+        let mut repeats = header(4, 1);
+        repeats.extend([9, 9, 9, 128]);
+        for _ in 0..20 {
+            repeats.extend([1, 1, 1, 0]);
+        }
+        assert!(Framebuffer::decode_hdr(&repeats).is_err());
+        // a repeat longer than the scanline only fills it
+        let mut long = header(4, 1);
+        long.extend([9, 9, 9, 128, 1, 1, 1, 200]);
+        assert_eq!(Framebuffer::decode_hdr(&long).unwrap().pixels, vec![from_rgbe([9, 9, 9, 128]); 4]);
+
+        for (width, height) in [(usize::MAX, 2), (1 << 20, 1 << 20)] {
+            let mut huge = header(width, height);
+            huge.extend([0; 16]);
+            assert!(Framebuffer::decode_hdr(&huge).is_err());
+        }
+    }
+
+    #[test]
+    fn exr_rejects_huge_data_windows() {
+        let mut bytes = Framebuffer::construct(2, 2).encode_exr(ExrCompression::None);
+        // the dataWindow's x_max and y_max, which follow its name, type and size
+        let at = bytes.windows(11).position(|w| w == b"dataWindow\0").unwrap() + 11 + 6 + 4 + 8;
+        bytes[at..at + 8].copy_from_slice(&[0xFF, 0xFF, 0xFF, 0x7F, 0xFF, 0xFF, 0xFF, 0x7F]);
+        assert!(Framebuffer::decode_exr(&bytes).is_err());
+    }
+
+    #[test]
+    fn exr_round_trip() {
+        // 37 lines leave a last ZIP block of 5
+        let image = test_image(9, 37);
+        let (raw, zip) = (image.encode_exr(ExrCompression::None), image.encode_exr(ExrCompression::Zip));
+        assert!(zip.len() < raw.len(), "ZIP blocks were stored raw");
+        assert_exact(&image, &Framebuffer::decode_exr(&raw).unwrap());
+        assert_exact(&image, &Framebuffer::decode_exr(&zip).unwrap());
+
+        assert!(Framebuffer::decode_exr(&Framebuffer::construct(0, 3).encode_exr(ExrCompression::None)).is_err());
+    }
+}
@@ -0,0 +1,247 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::utils::{Color3, BLACK};
+use crate::deflate::{zlib_compress, crc32};
+use crate::hdr::ExrCompression;
+
+/// Linear radiance per pixel, row order with the top row first
+#[derive(Clone, Debug)]
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color3>
+}
+
+/// Image file formats the [`Framebuffer`] can be written in.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ImageFormat {
+    Ppm,  // binary P6
+    Png,
+    Bmp,
+    Tga,
+    Pfm,
+    Hdr,  // Radiance RGBE
+    Exr   // ZIP compressed
+}
+
+impl ImageFormat {
+    pub fn from_extension(extension: &str) -> Option<ImageFormat> {
+        match extension.to_ascii_lowercase().as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            "bmp" => Some(ImageFormat::Bmp),
+            "tga" => Some(ImageFormat::Tga),
+            "pfm" => Some(ImageFormat::Pfm),
+            "hdr" => Some(ImageFormat::Hdr),
+            "exr" => Some(ImageFormat::Exr),
+            _ => None
+        }
+    }
+    pub fn from_path(path: &Path) -> Option<ImageFormat> {
+        Self::from_extension(path.extension()?.to_str()?)
+    }
+}
+
+impl Framebuffer {
+    pub fn construct(width: usize, height: usize) -> Framebuffer {
+        Framebuffer {width, height, pixels: vec![BLACK; width * height]}
+    }
+    pub fn get(&self, x: usize, y: usize) -> Color3 {
+        self.pixels[y * self.width + x]
+    }
+    pub fn set(&mut self, x: usize, y: usize, color: Color3) {
+        self.pixels[y * self.width + x] = color;
+    }
+    pub fn print_ascii_ppm(&self) {
+        println!("P3\n{} {}", self.width, self.height);
+        println!("255");
+        for pixel in &self.pixels {
+            pixel.print_out();
+        }
+    }
+    /// 8 bit RGB triples, row order with the top row first.
+    pub fn to_rgb8(&self) -> Vec<u8> {
+        self.pixels.iter().flat_map(|pixel| pixel.to_rgb8()).collect()
+    }
+    pub fn encode(&self, format: ImageFormat) -> Vec<u8> {
+        match format {
+            ImageFormat::Ppm => self.encode_ppm(),
+            ImageFormat::Png => self.encode_png(),
+            ImageFormat::Bmp => self.encode_bmp(),
+            ImageFormat::Tga => self.encode_tga(),
+            ImageFormat::Pfm => self.encode_pfm(),
+            ImageFormat::Hdr => self.encode_hdr(),
+            ImageFormat::Exr => self.encode_exr(ExrCompression::Zip)
+        }
+    }
+    /// Writes the image in the format given by the file extension.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let format = ImageFormat::from_path(path).ok_or_else(|| io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unsupported image format for {}", path.display())
+        ))?;
+        fs::write(path, self.encode(format))
+    }
+    pub fn encode_ppm(&self) -> Vec<u8> {
+        let mut out = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        out.extend(self.to_rgb8());
+        out
+    }
+    pub fn encode_png(&self) -> Vec<u8> {
+        fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+            out.extend((data.len() as u32).to_be_bytes());
+            let start = out.len();
+            out.extend(kind);
+            out.extend(data);
+            out.extend(crc32(&out[start..]).to_be_bytes());
+        }
+        let mut header = Vec::with_capacity(13);
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        header.extend([8, 2, 0, 0, 0]);  // 8 bit depth, truecolour, deflate, adaptive filtering, no interlace
+
+        // each scanline gets whichever filter leaves the smallest sum of absolute residuals
+        let rgb = self.to_rgb8();
+        let stride = self.width * 3;
+        let mut filtered = Vec::with_capacity((stride + 1) * self.height);
+        let zeros = vec![0u8; stride];
+        for y in 0..self.height {
+            let line = &rgb[y * stride..(y + 1) * stride];
+            let above = if y > 0 { &rgb[(y - 1) * stride..y * stride] } else { &zeros[..] };
+            let candidates: Vec<Vec<u8>> = (0..5u8).map(|filter| (0..stride).map(|i| {
+                let a = if i >= 3 { line[i - 3] } else { 0 };
+                let b = above[i];
+                let c = if i >= 3 { above[i - 3] } else { 0 };
+                let predicted = match filter {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((a as u16 + b as u16) / 2) as u8,
+                    _ => paeth(a, b, c)
+                };
+                line[i].wrapping_sub(predicted)
+            }).collect()).collect();
+            let cost = |residuals: &Vec<u8>| residuals.iter().map(|&r| (r as i8).unsigned_abs() as u64).sum::<u64>();
+            let (filter, residuals) = candidates.iter().enumerate().min_by_key(|(_, r)| cost(r)).unwrap();
+            filtered.push(filter as u8);
+            filtered.extend(residuals);
+        }
+
+        let mut out = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        chunk(&mut out, b"IHDR", &header);
+        chunk(&mut out, b"IDAT", &zlib_compress(&filtered));
+        chunk(&mut out, b"IEND", &[]);
+        out
+    }
+    pub fn encode_bmp(&self) -> Vec<u8> {
+        // 24 bit BI_RGB: BGR, rows bottom up and padded to 4 bytes
+        let stride = (self.width * 3).div_ceil(4) * 4;
+        let size = 54 + stride * self.height;
+        let mut out = Vec::with_capacity(size);
+        out.extend(b"BM");
+        out.extend((size as u32).to_le_bytes());
+        out.extend([0; 4]);
+        out.extend(54u32.to_le_bytes());  // pixel data offset
+        out.extend(40u32.to_le_bytes());  // BITMAPINFOHEADER
+        out.extend((self.width as i32).to_le_bytes());
+        out.extend((self.height as i32).to_le_bytes());
+        out.extend(1u16.to_le_bytes());   // planes
+        out.extend(24u16.to_le_bytes());  // bits per pixel
+        out.extend(0u32.to_le_bytes());   // no compression
+        out.extend(((stride * self.height) as u32).to_le_bytes());
+        out.extend(2835i32.to_le_bytes());  // 72 dpi
+        out.extend(2835i32.to_le_bytes());
+        out.extend([0; 8]);  // palette sizes
+        for y in (0..self.height).rev() {
+            let start = out.len();
+            for x in 0..self.width {
+                let [r, g, b] = self.get(x, y).to_rgb8();
+                out.extend([b, g, r]);
+            }
+            out.resize(start + stride, 0);
+        }
+        out
+    }
+    pub fn encode_tga(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(18 + self.pixels.len() * 3);
+        out.extend([0, 0, 2]);  // no id, no colour map, uncompressed truecolour
+        out.extend([0; 5]);     // colour map spec
+        out.extend([0; 4]);     // origin
+        out.extend((self.width as u16).to_le_bytes());
+        out.extend((self.height as u16).to_le_bytes());
+        out.extend([24, 0x20]);  // bits per pixel, top left origin
+        for pixel in &self.pixels {
+            let [r, g, b] = pixel.to_rgb8();
+            out.extend([b, g, r]);
+        }
+        out
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deflate::zlib_decompress;
+
+    #[test]
+    fn png_chunks_and_pixels() {
+        let mut image = Framebuffer::construct(13, 7);
+        for y in 0..image.height {
+            for x in 0..image.width {
+                image.set(x, y, Color3 {r: x as f64 / 13.0, g: y as f64 / 7.0, b: ((x * y) % 3) as f64 / 2.0});
+            }
+        }
+        let png = image.encode_png();
+        assert_eq!(png[..8], [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]);
+
+        let mut chunks = Vec::new();
+        let mut rest = &png[8..];
+        while !rest.is_empty() {
+            let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let (kind, data) = (&rest[4..8], &rest[8..8 + length]);
+            let crc = u32::from_be_bytes(rest[8 + length..12 + length].try_into().unwrap());
+            assert_eq!(crc, crc32(&rest[4..8 + length]), "bad CRC in {}", String::from_utf8_lossy(kind));
+            chunks.push((kind.to_vec(), data.to_vec()));
+            rest = &rest[12 + length..];
+        }
+        let kinds: Vec<&[u8]> = chunks.iter().map(|(kind, _)| &kind[..]).collect();
+        assert_eq!(kinds, [b"IHDR", b"IDAT", b"IEND"]);
+        assert_eq!(chunks[0].1, [0, 0, 0, 13, 0, 0, 0, 7, 8, 2, 0, 0, 0]);
+
+        // undo the filters, scanline by scanline
+        let filtered = zlib_decompress(&chunks[1].1).unwrap();
+        let stride = image.width * 3;
+        assert_eq!(filtered.len(), (stride + 1) * image.height);
+        let mut rgb: Vec<u8> = Vec::new();
+        for (y, line) in filtered.chunks(stride + 1).enumerate() {
+            for i in 0..stride {
+                let a = if i >= 3 { rgb[y * stride + i - 3] } else { 0 };
+                let b = if y > 0 { rgb[(y - 1) * stride + i] } else { 0 };
+                let c = if i >= 3 && y > 0 { rgb[(y - 1) * stride + i - 3] } else { 0 };
+                let predicted = match line[0] {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((a as u16 + b as u16) / 2) as u8,
+                    4 => paeth(a, b, c),
+                    filter => panic!("invalid filter {}", filter)
+                };
+                rgb.push(line[1 + i].wrapping_add(predicted));
+            }
+        }
+        assert_eq!(rgb, image.to_rgb8());
+    }
+}
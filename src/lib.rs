@@ -0,0 +1,69 @@
+//! An elementary raytracer, after
+//! [Ray Tracing in One Weekend](https://raytracing.github.io/books/RayTracingInOneWeekend.html).
+//!
+//! A [`World`] holds [`Object`]s, each with a [`Material`]; a [`Camera`] renders it into a
+//! [`Framebuffer`] of linear radiance, which can be written as an 8 bit or HDR image.
+//! Scenes can also be described in a text file and loaded with [`Scene::load`].
+//!
+//! ```no_run
+//! use raytracing::prelude::*;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let world = World::construct(vec![
+//!     Box::new(Sphere::construct(Point3::construct(0.0, 1.0, 0.0), 0.5, Box::new(Diffuse::construct(0.8, 0.2, 0.2)?))?),
+//!     Box::new(Plane::construct(Point3::construct(0.0, 0.0, -0.5), UNIT_Z, Box::new(Diffuse::construct(0.5, 0.5, 0.5)?))?)
+//! ]);
+//! let mut scene = Scene::construct(world);
+//! scene.settings.width = Some(320);
+//! scene.camera()?.render().write(std::path::Path::new("out.png"))?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod error;
+pub mod utils;
+pub mod random;
+pub mod camera;
+pub mod object;
+pub mod material;
+pub mod light;
+pub mod background;
+pub mod environment;
+pub mod sky;
+pub mod obj;
+pub mod bvh;
+pub mod image;
+pub mod deflate;
+pub mod hdr;
+pub mod scene;
+
+pub use error::RaytraceError;
+pub use random::Pcg32;
+pub use utils::{Point3, Color3, Ray, RayHit, Face, Material, UNIT_X, UNIT_Y, UNIT_Z, ORIGIN, BLACK, WHITE};
+pub use camera::{Camera, View, Projection, Aperture, Focus, RenderSettings};
+pub use object::{World, Object, Sphere, Plane, Triangle, Quad, Disk, LightSampling, Mesh, Moving};
+pub use material::{Metallic, Diffuse, Dielectric, LightSource};
+pub use light::{Light, LightSample, PointLight, SpotLight, DirectionalLight};
+pub use background::{Background, Solid, Black, Gradient, Checker};
+pub use environment::EnvironmentMap;
+pub use sky::Sky;
+pub use bvh::{Aabb, Bvh};
+pub use image::{Framebuffer, ImageFormat};
+pub use hdr::ExrCompression;
+pub use obj::ObjError;
+pub use scene::{Scene, SceneError};
+
+/// Everything needed to build and render a scene: `use raytracing::prelude::*;`
+pub mod prelude {
+    pub use crate::error::RaytraceError;
+    pub use crate::utils::{Point3, Color3, Ray, RayHit, Face, Material, UNIT_X, UNIT_Y, UNIT_Z, ORIGIN, BLACK, WHITE};
+    pub use crate::camera::{Camera, View, Projection, Aperture, Focus, RenderSettings};
+    pub use crate::object::{World, Object, Sphere, Plane, Triangle, Quad, Disk, LightSampling, Mesh, Moving};
+    pub use crate::material::{Metallic, Diffuse, Dielectric, LightSource};
+    pub use crate::light::{Light, PointLight, SpotLight, DirectionalLight};
+    pub use crate::background::{Background, Solid, Black, Gradient, Checker};
+    pub use crate::environment::EnvironmentMap;
+    pub use crate::sky::Sky;
+    pub use crate::image::{Framebuffer, ImageFormat};
+    pub use crate::scene::Scene;
+}
@@ -0,0 +1,191 @@
+use crate::utils::{Point3, Color3, MINIMUM, sample_cone};
+use crate::error::RaytraceError;
+
+/// Light that is not an object: rays never hit it, it only reaches surfaces through shadow rays.
+pub trait Light: Send + Sync {
+    /// The direction towards the light from `point`, with `u` uniform in [0, 1)^2; None where it gives no light.
+    fn sample(&self, point: Point3, u: (f64, f64)) -> Option<LightSample>;
+}
+
+/// Light arriving at a point from one direction.
+#[derive(Copy, Clone, Debug)]
+pub struct LightSample {
+    pub direction: Point3,  // unit, towards the light
+    pub distance: f64,      // to the light, infinite for directional lights
+    pub radiance: Color3    // divided by the density the direction was picked with
+}
+
+fn emission(color: Color3, intensity: f64) -> Result<(), RaytraceError> {
+    Color3::construct(color.r, color.g, color.b)?;
+    if !intensity.is_finite() || intensity < 0.0 {
+        return Err(RaytraceError::InvalidIntensity(intensity));
+    }
+    Ok(())
+}
+
+fn unit_direction(direction: Point3) -> Result<Point3, RaytraceError> {
+    let unit = direction.unit_vector();
+    if !(unit.x.is_finite() && unit.y.is_finite() && unit.z.is_finite()) {
+        return Err(RaytraceError::ZeroDirection);
+    }
+    Ok(unit)
+}
+
+/// Light from a single point in all directions, falling off with the square of the distance.
+pub struct PointLight {
+    pub position: Point3,
+    pub color: Color3,
+    pub intensity: f64
+}
+
+impl PointLight {
+    pub fn construct(position: Point3, color: Color3, intensity: f64) -> Result<PointLight, RaytraceError> {
+        emission(color, intensity)?;
+        Ok(PointLight {position, color, intensity})
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, point: Point3, _u: (f64, f64)) -> Option<LightSample> {
+        let to_light = self.position - point;
+        let distance_square = to_light.norm_square();
+        if distance_square < MINIMUM {
+            return None;  // at the light itself
+        }
+        let distance = distance_square.sqrt();
+        Some(LightSample {direction: to_light / distance, distance, radiance: self.color * (self.intensity / distance_square)})
+    }
+}
+
+/// Point light shining into a cone of `angle` degrees about `direction`, fading out over the
+/// last `falloff` degrees towards the edge.
+pub struct SpotLight {
+    pub position: Point3,
+    pub direction: Point3,  // unit
+    pub angle: f64,         // half angle of the cone, degrees
+    pub falloff: f64,       // degrees
+    pub color: Color3,
+    pub intensity: f64
+}
+
+impl SpotLight {
+    pub fn construct(position: Point3, direction: Point3, angle: f64, falloff: f64, color: Color3, intensity: f64) -> Result<SpotLight, RaytraceError> {
+        emission(color, intensity)?;
+        if !(angle > 0.0 && angle <= 180.0) {
+            return Err(RaytraceError::InvalidConeAngle(angle));
+        }
+        if !(0.0..=angle).contains(&falloff) {
+            return Err(RaytraceError::InvalidFalloff(falloff));
+        }
+        Ok(SpotLight {position, direction: unit_direction(direction)?, angle, falloff, color, intensity})
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, point: Point3, _u: (f64, f64)) -> Option<LightSample> {
+        let to_light = self.position - point;
+        let distance_square = to_light.norm_square();
+        if distance_square < MINIMUM {
+            return None;  // at the light itself
+        }
+        let distance = distance_square.sqrt();
+        let direction = to_light / distance;
+
+        let cos = -direction.dot(self.direction);
+        let cos_outer = self.angle.to_radians().cos();
+        let cos_inner = (self.angle - self.falloff).to_radians().cos();
+        let strength = if cos >= cos_inner {
+            1.0
+        } else {
+            let t = ((cos - cos_outer) / (cos_inner - cos_outer)).clamp(0.0, 1.0);
+            t*t*(3.0 - 2.0*t)  // smoothstep
+        };
+        if strength == 0.0 {
+            return None;
+        }
+        Some(LightSample {direction, distance, radiance: self.color * (self.intensity * strength / distance_square)})
+    }
+}
+
+/// Light from infinitely far away, like the sun; an angular diameter above 0 gives soft shadows.
+pub struct DirectionalLight {
+    pub direction: Point3,       // unit, the way the light travels
+    pub angular_diameter: f64,   // degrees
+    pub color: Color3,
+    pub intensity: f64           // irradiance on a surface facing the light
+}
+
+impl DirectionalLight {
+    pub fn construct(direction: Point3, angular_diameter: f64, color: Color3, intensity: f64) -> Result<DirectionalLight, RaytraceError> {
+        emission(color, intensity)?;
+        if !(0.0..180.0).contains(&angular_diameter) {
+            return Err(RaytraceError::InvalidAngularDiameter(angular_diameter));
+        }
+        Ok(DirectionalLight {direction: unit_direction(direction)?, angular_diameter, color, intensity})
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _point: Point3, u: (f64, f64)) -> Option<LightSample> {
+        let direction = match self.angular_diameter {
+            0.0 => -self.direction,
+            diameter => sample_cone(-self.direction, (diameter / 2.0).to_radians().cos(), u)
+        };
+        // uniform over the disk, so radiance over density is the irradiance
+        Some(LightSample {direction, distance: f64::INFINITY, radiance: self.color * self.intensity})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{ORIGIN, UNIT_Z};
+
+    const ORANGE: Color3 = Color3 {r: 1.0, g: 0.5, b: 0.25};
+
+    fn assert_color(color: Color3, expected: (f64, f64, f64)) {
+        let (r, g, b) = expected;
+        assert!((color.r - r).abs() < 1e-12 && (color.g - g).abs() < 1e-12 && (color.b - b).abs() < 1e-12, "{:?} vs {:?}", color, expected);
+    }
+
+    #[test]
+    fn point_lights_fall_off_with_the_square_of_the_distance() {
+        let light = PointLight::construct(Point3 {x: 0.0, y: 0.0, z: 2.0}, ORANGE, 8.0).unwrap();
+        let sample = light.sample(ORIGIN, (0.5, 0.5)).unwrap();
+        assert!((sample.direction - UNIT_Z).norm() < 1e-12);
+        assert_eq!(sample.distance, 2.0);
+        assert_color(sample.radiance, (2.0, 1.0, 0.5));
+        assert_color(light.sample(Point3 {x: 4.0, y: 0.0, z: 2.0}, (0.5, 0.5)).unwrap().radiance, (0.5, 0.25, 0.125));
+        assert!(light.sample(light.position, (0.5, 0.5)).is_none());
+    }
+
+    #[test]
+    fn spot_lights_fade_out_towards_the_edge() {
+        // straight down from height 1, full out to 20 degrees and nothing past 30
+        let light = SpotLight::construct(UNIT_Z, -UNIT_Z, 30.0, 10.0, ORANGE, 4.0).unwrap();
+        let at = |degrees: f64| light.sample(Point3 {x: degrees.to_radians().tan(), y: 0.0, z: 0.0}, (0.5, 0.5));
+        assert_color(at(0.0).unwrap().radiance, (4.0, 2.0, 1.0));
+        let inner = at(19.0).unwrap();
+        let distance_square = 1.0 / 19.0f64.to_radians().cos().powi(2);
+        assert_color(inner.radiance, (4.0 / distance_square, 2.0 / distance_square, 1.0 / distance_square));
+        let (fading, faded) = (at(24.0).unwrap().radiance.r, at(28.0).unwrap().radiance.r);
+        assert!(0.0 < faded && faded < fading && fading < inner.radiance.r);
+        assert!(at(31.0).is_none());
+    }
+
+    #[test]
+    fn directional_lights_come_from_one_way_or_a_disk() {
+        let light = DirectionalLight::construct(-UNIT_Z, 0.0, ORANGE, 3.0).unwrap();
+        let sample = light.sample(ORIGIN, (0.2, 0.7)).unwrap();
+        assert!((sample.direction - UNIT_Z).norm() < 1e-12);
+        assert_eq!(sample.distance, f64::INFINITY);
+        assert_color(sample.radiance, (3.0, 1.5, 0.75));
+
+        let soft = DirectionalLight::construct(-UNIT_Z, 10.0, ORANGE, 3.0).unwrap();
+        let directions: Vec<Point3> = [(0.1, 0.2), (0.9, 0.5), (0.5, 0.99), (0.99, 0.01)].iter()
+            .map(|&u| soft.sample(ORIGIN, u).unwrap().direction)
+            .collect();
+        assert!(directions.iter().all(|d| d.z >= 5.0f64.to_radians().cos() - 1e-12));
+        assert!(directions.iter().any(|d| d.z < 1.0 - 1e-6));
+    }
+}
@@ -1,69 +1,58 @@
-mod utils;
-mod camera;
-mod object;
-mod material;
-use utils::{Point3, UNIT_Y, ORIGIN};
-use camera::Camera;
-use object::{World, Sphere, Triangle, Plane};
-use material::{Metallic, Diffuse, Dielectric, LightSource};
+use std::io::Write;
+use std::process::ExitCode;
+use raytracing::Scene;
 
-fn main() {
-    let image_width = 512;
-    let image_height = image_width*9/16;
-    let pixel_samples = 3; // for anti-aliasing (1 => no anti-aliasing)
-    let scatter_depth = 10;
-    
-    let world = World {
-        objects: vec![
-            // ground
-            Box::new(
-                Triangle {  // uh its a plane
-                    material: Box::new(Diffuse::construct(0.8, 0.8, 0.0)),
-                    // Box::new(Dielectric {refractive_index: 1.5}),
-                    // Box::new(Metallic::construct(0.8, 0.8, 0.0, 0.1)),
-                    plane: Plane::construct(0.0, 0.0, -0.5, Point3 {x: 0.0, y: 0.0, z: -2.0})
-                }
-            ),
-            // Box::new(Sphere {
-            //     position: Point3::construct(0.0, 1.0, -100.5),
-            //     radius: 100.0,
-            //     material: Box::new(Diffuse::construct(0.8, 0.8, 0.0))
-            // }),
-            // middle
-            Box::new(Sphere {
-                position: Point3::construct(0.0, 1.2, 0.0),
-                radius: 0.5,
-                material: Box::new(Diffuse::construct(0.1, 0.2, 0.5))
-            }),
-            // left outer
-            Box::new(Sphere {
-                position: Point3::construct(-1.0, 1.0, 0.0),
-                radius: 0.5,
-                material: 
-                // Box::new(LightSource::construct())
-                Box::new(Dielectric {refractive_index: 1.5})
-                // Box::new(Metallic::construct(0.8, 0.8, 0.8, 0.3))
-            }),
-            // left inner
-            Box::new(Sphere {
-                position: Point3::construct(-1.0, 1.0, 0.0),
-                radius: 0.4,
-                material: Box::new(Dielectric {refractive_index: 1.0/1.5})
-            }),
-            // right
-            Box::new(Sphere {
-                position: Point3::construct(1.0, 1.0, 0.0),
-                radius: 0.5,
-                material: Box::new(Metallic::construct(0.8, 0.6, 0.2, 0.1))
-            })
-        ]
+mod cli;
+use cli::{Command, USAGE};
+
+fn main() -> ExitCode {
+    let settings = match cli::parse(std::env::args().skip(1)) {
+        Ok(Command::Render(settings)) => settings,
+        Ok(Command::Help) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        },
+        Err(message) => {
+            eprintln!("error: {}\n\nRun with --help for usage.", message);
+            return ExitCode::from(2);
+        }
+    };
+
+    let loaded: Result<Scene, Box<dyn std::error::Error>> = match &settings.scene {
+        Some(path) => Scene::load(path).map_err(Into::into),
+        None => Scene::demo().map_err(Into::into)
+    };
+    let mut scene = match loaded {
+        Ok(scene) => scene,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(message) = settings.apply(&mut scene) {
+        eprintln!("error: {}\n\nRun with --help for usage.", message);
+        return ExitCode::from(2);
+    }
+    let image = match scene.camera() {
+        Ok(camera) => camera.render(),
+        Err(error) => {
+            eprintln!("error: {}", error);
+            return ExitCode::FAILURE;
+        }
     };
 
-    let mut camera = Camera::construct(&world, image_width, image_height, 
-        ORIGIN, // camera position
-        UNIT_Y, // viewport (center) position
-        Point3 {x: 2.0*(image_width as f64 / image_height as f64), z: -2.0, y: 0.0}, // viewport diagonal
-        pixel_samples, scatter_depth
-    );
-    camera.render_ascii_ppm();
+    let written = match (&settings.output, settings.format) {
+        (Some(path), Some(format)) => std::fs::write(path, image.encode(format)),
+        (Some(path), None) => image.write(path),
+        (None, Some(format)) => std::io::stdout().write_all(&image.encode(format)),
+        (None, None) => {
+            image.print_ascii_ppm();
+            Ok(())
+        }
+    };
+    if let Err(error) = written {
+        eprintln!("error: cannot write image: {}", error);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
 }
@@ -1,79 +1,108 @@
-use crate::utils::{Point3, Ray, Color3, RayHit, Face, Material, MINIMUM, INFINITY};
+use std::f64::consts::PI;
+use rand::{Rng, RngCore};
+use crate::utils::{Point3, Ray, Color3, RayHit, Face, Material, BLACK, MINIMUM};
+use crate::error::RaytraceError;
+
+fn albedo(r: f64, g: f64, b: f64) -> Result<Color3, RaytraceError> {
+    if [r, g, b].iter().any(|c| !(0.0..=1.0).contains(c)) {
+        return Err(RaytraceError::InvalidAlbedo {r, g, b});
+    }
+    Ok(Color3 {r, g, b})
+}
+
+// uniformly distributed over the unit sphere
+fn random_unit_vector(rng: &mut dyn RngCore) -> Point3 {
+    loop {
+        let p = Point3 {x: rng.random_range(-1.0..1.0), y: rng.random_range(-1.0..1.0), z: rng.random_range(-1.0..1.0)};
+        let norm_square = p.norm_square();
+        if norm_square > 1e-12 && norm_square <= 1.0 {
+            return p / norm_square.sqrt();
+        }
+    }
+}
 
+/// Reflective metal, with the reflection blurred by `fuzz`.
 pub struct Metallic {
-    albedo: Point3,
+    albedo: Color3,
     fuzz: f64
 }
 
 impl Metallic {
-    pub fn construct(r_: f64, g_: f64, b_: f64, fuzz: f64) -> Metallic {
-        for c in vec![r_, g_, b_, fuzz] {
-            if c < 0.0 || c > 1.0 {
-                panic!("Invalid albedo (for metal)! r={} g={} b={} fuzz={}", r_, g_, b_, fuzz);
-            }
+    pub fn construct(r_: f64, g_: f64, b_: f64, fuzz: f64) -> Result<Metallic, RaytraceError> {
+        if !(0.0..=1.0).contains(&fuzz) {
+            return Err(RaytraceError::InvalidFuzz(fuzz));
         }
-        Metallic {albedo: Point3 {x: r_, y: g_, z: b_}, fuzz}
+        Ok(Metallic {albedo: albedo(r_, g_, b_)?, fuzz})
     }
 }
 
 impl Material for Metallic {
-    fn scatter(&self, ray: &Ray, hit: &RayHit) -> Option<Ray> {
-        let RayHit::Hit {normal, point, ..} = *hit else { panic!("metallic scatter attempted on a NoHit") };
+    fn scatter(&self, ray: &Ray, hit: &RayHit, rng: &mut dyn RngCore) -> Option<Ray> {
+        let RayHit {normal, point, ..} = *hit;
         let mut reflected_vec = ray.A - normal*normal.dot(ray.A)*2.0;
         if reflected_vec.norm() < MINIMUM {
             reflected_vec = normal;
         }
-        let random_vec = Point3 {x: rand::random(), y: rand::random(), z: rand::random()}; // TODO: INEFFICIENT
-        reflected_vec = reflected_vec.unit_vector() + random_vec.unit_vector() * self.fuzz;
+        reflected_vec = reflected_vec.unit_vector() + random_unit_vector(rng) * self.fuzz;
 
-        Some(Ray::construct(reflected_vec, point))
+        Some(Ray::construct(reflected_vec, point).with_time(ray.time))
     }
 
     fn attenuate(&self, color: Color3) -> Color3 {
-        Color3::from_point3(self.albedo * color.to_point3())
+        color * self.albedo
     }
 }
 
+/// Lambertian (matte) surface.
 pub struct Diffuse {
-    albedo: Point3
+    albedo: Color3
 }
 
 impl Diffuse {
-    pub fn construct(r_: f64, g_: f64, b_: f64) -> Diffuse {
-        for c in vec![r_, g_, b_] {
-            if c < 0.0 || c > 1.0 {
-                panic!("Invalid albedo (for diffuse material)! r={} g={} b={}", r_, g_, b_);
-            }
-        }
-        Diffuse {albedo: Point3 {x: r_, y: g_, z: b_}}
+    pub fn construct(r_: f64, g_: f64, b_: f64) -> Result<Diffuse, RaytraceError> {
+        Ok(Diffuse {albedo: albedo(r_, g_, b_)?})
     }
 }
 
 impl Material for Diffuse {
-    fn scatter(&self, _ray: &Ray, hit: &RayHit) -> Option<Ray> {
-        let RayHit::Hit {normal, point, ..} = *hit else { panic!("diffuse scatter attempted on a NoHit") };
-        let mut random_vec = Point3 {x: rand::random(), y: rand::random(), z: rand::random()}; // TODO: INEFFICIENT
-        random_vec = random_vec.unit_vector() + normal;
-        
-        if random_vec.norm() < MINIMUM && random_vec.norm() >= INFINITY {
+    fn scatter(&self, ray: &Ray, hit: &RayHit, rng: &mut dyn RngCore) -> Option<Ray> {
+        let RayHit {normal, point, ..} = *hit;
+        // cosine weighted about the normal
+        let mut random_vec = random_unit_vector(rng) + normal;
+        if random_vec.norm() < MINIMUM {
             random_vec = normal;
         }
 
-        Some(Ray::construct(random_vec, point))
+        Some(Ray::construct(random_vec, point).with_time(ray.time))
     }
 
     fn attenuate(&self, color: Color3) -> Color3 {
-        Color3::from_point3(self.albedo * color.to_point3())
+        color * self.albedo
+    }
+
+    fn bsdf(&self, _ray: &Ray, hit: &RayHit, direction: Point3) -> Option<(Color3, f64)> {
+        let cos = hit.normal.dot(direction).max(0.0);
+        Some((self.albedo * (cos / PI), cos / PI))
     }
 }
 
+/// Glass-like material that refracts or reflects, by Schlick's approximation.
 pub struct Dielectric {
     pub refractive_index: f64
 }
 
+impl Dielectric {
+    pub fn construct(refractive_index: f64) -> Result<Dielectric, RaytraceError> {
+        if !refractive_index.is_finite() || refractive_index <= 0.0 {
+            return Err(RaytraceError::InvalidRefractiveIndex(refractive_index));
+        }
+        Ok(Dielectric {refractive_index})
+    }
+}
+
 impl Material for Dielectric {
-    fn scatter(&self, ray: &Ray, hit: &RayHit) -> Option<Ray> {
-        let RayHit::Hit {normal, point, face, ..} = *hit else { panic!("diffuse scatter attempted on a NoHit") };
+    fn scatter(&self, ray: &Ray, hit: &RayHit, rng: &mut dyn RngCore) -> Option<Ray> {
+        let RayHit {normal, point, face, ..} = *hit;
         let refractive_ratio = match face {
             Face::FrontFace => 1.0/self.refractive_index,
             Face::BackFace => self.refractive_index
@@ -85,7 +114,7 @@ impl Material for Dielectric {
             let r = ((1.0 - refractive_ratio)/(1.0 + refractive_ratio)).powf(2.0);
             r + (1.0 - r)*(1.0 - cos).powf(5.0)
         };
-        let refracted = if sin * refractive_ratio > 1.0 || reflectance > rand::random_range(0.0..=1.0) {
+        let refracted = if sin * refractive_ratio > 1.0 || reflectance > rng.random_range(0.0..=1.0) {
             ray.A - normal*normal.dot(ray.A)*2.0  // total internal reflection
         } else {
             let refracted_y = normal.cross(ray.A.cross(normal) * refractive_ratio);
@@ -93,7 +122,7 @@ impl Material for Dielectric {
             refracted_x + refracted_y
         };
 
-        Some(Ray::construct(refracted, point))
+        Some(Ray::construct(refracted, point).with_time(ray.time))
     }
 
     fn attenuate(&self, color: Color3) -> Color3 { // TODO: tinted glass?
@@ -101,22 +130,43 @@ impl Material for Dielectric {
     }
 }
 
+/// Emitter, giving off its color times intensity and reflecting nothing; from both sides of a
+/// surface unless made one-sided, when only its front face (along the normal) emits.
 pub struct LightSource {
-    color: Color3
+    pub color: Color3,
+    pub intensity: f64,
+    pub two_sided: bool
 }
 
 impl LightSource {
-    pub fn construct() -> LightSource {
-        LightSource {color: Color3::construct(1.0, 1.0, 1.0)}
+    pub fn construct(r: f64, g: f64, b: f64, intensity: f64) -> Result<LightSource, RaytraceError> {
+        if !intensity.is_finite() || intensity < 0.0 {
+            return Err(RaytraceError::InvalidIntensity(intensity));
+        }
+        Ok(LightSource {color: Color3::construct(r, g, b)?, intensity, two_sided: true})
+    }
+    pub fn one_sided(self) -> LightSource {
+        LightSource {two_sided: false, ..self}
     }
 }
 
 impl Material for LightSource {
-    fn scatter(&self, _ray: &Ray, _hit: &RayHit) -> Option<Ray> {
+    fn scatter(&self, _ray: &Ray, _hit: &RayHit, _rng: &mut dyn RngCore) -> Option<Ray> {
         None
     }
 
     fn attenuate(&self, _color: Color3) -> Color3 {
-        self.color
+        BLACK
+    }
+
+    fn emitted(&self, hit: &RayHit) -> Color3 {
+        match hit.face {
+            Face::BackFace if !self.two_sided => BLACK,
+            _ => self.color * self.intensity
+        }
     }
-}
\ No newline at end of file
+
+    fn is_emitter(&self) -> bool {
+        true
+    }
+}
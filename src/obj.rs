@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::utils::{Point3, Material};
+use crate::object::Mesh;
+use crate::material::{Metallic, Diffuse, Dielectric, LightSource};
+use crate::error::RaytraceError;
+
+// Wavefront .obj/.mtl loading: https://paulbourke.net/dataformats/obj/
+// Each (group, material) run of faces becomes one Mesh; polygons are fan triangulated.
+
+/// Error loading a Wavefront file, with the line for parse errors.
+#[derive(Debug)]
+pub enum ObjError {
+    Io {file: PathBuf, error: std::io::Error},
+    Parse {file: PathBuf, line: usize, message: String},
+    Invalid {file: PathBuf, error: RaytraceError}  // well formed, but not a valid mesh or material
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io {file, error} => write!(f, "{}: {}", file.display(), error),
+            ObjError::Parse {file, line, message} => write!(f, "{}:{}: {}", file.display(), line, message),
+            ObjError::Invalid {file, error} => write!(f, "{}: {}", file.display(), error)
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+// what an .mtl entry says, kept around so every mesh using it can get its own Box<dyn Material>
+#[derive(Debug, Clone)]
+pub struct MtlMaterial {
+    pub name: String,
+    pub diffuse: Point3,   // Kd
+    pub specular: Point3,  // Ks
+    pub emission: Point3,  // Ke
+    pub shininess: f64,    // Ns
+    pub refractive_index: Option<f64>,  // Ni
+    pub dissolve: f64,     // d (or 1 - Tr)
+    pub illum: i32
+}
+
+impl MtlMaterial {
+    fn construct(name: &str) -> MtlMaterial {
+        MtlMaterial {
+            name: name.to_string(),
+            diffuse: Point3::construct(0.8, 0.8, 0.8),
+            specular: Point3::construct(0.0, 0.0, 0.0),
+            emission: Point3::construct(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            refractive_index: None,
+            dissolve: 1.0,
+            illum: 2
+        }
+    }
+    pub fn to_material(&self) -> Result<Box<dyn Material>, RaytraceError> {
+        let clamped = |c: Point3| (c.x.clamp(0.0, 1.0), c.y.clamp(0.0, 1.0), c.z.clamp(0.0, 1.0));
+        let max = |c: Point3| c.x.max(c.y).max(c.z);
+
+        Ok(if max(self.emission) > 0.0 {
+            let e = self.emission;
+            Box::new(LightSource::construct(e.x.max(0.0), e.y.max(0.0), e.z.max(0.0), 1.0)?)
+        } else if self.dissolve < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9) {
+            Box::new(Dielectric::construct(self.refractive_index.unwrap_or(1.5))?)
+        } else if max(self.specular) > 0.0 && (self.illum == 3 || max(self.diffuse) <= 0.0) {
+            let (r, g, b) = clamped(self.specular);
+            let fuzz = (2.0 / (self.shininess.max(0.0) + 2.0)).sqrt();  // Blinn-Phong exponent to roughness
+            Box::new(Metallic::construct(r, g, b, fuzz.clamp(0.0, 1.0))?)
+        } else {
+            let (r, g, b) = clamped(self.diffuse);
+            Box::new(Diffuse::construct(r, g, b)?)
+        })
+    }
+}
+
+struct Parser<'a> {
+    file: &'a Path,
+    line: usize
+}
+
+impl Parser<'_> {
+    fn error(&self, message: String) -> ObjError {
+        ObjError::Parse {file: self.file.to_path_buf(), line: self.line, message}
+    }
+    fn float(&self, token: Option<&str>, what: &str) -> Result<f64, ObjError> {
+        let token = token.ok_or_else(|| self.error(format!("missing {}", what)))?;
+        match token.parse::<f64>() {
+            Ok(value) if value.is_finite() => Ok(value),
+            _ => Err(self.error(format!("invalid number '{}' for {}", token, what)))
+        }
+    }
+    fn point(&self, tokens: &mut std::str::SplitWhitespace, what: &str) -> Result<Point3, ObjError> {
+        Ok(Point3 {
+            x: self.float(tokens.next(), what)?,
+            y: self.float(tokens.next(), what)?,
+            z: self.float(tokens.next(), what)?
+        })
+    }
+    fn name(&self, tokens: std::str::SplitWhitespace, what: &str) -> Result<String, ObjError> {
+        let name = tokens.collect::<Vec<_>>().join(" ");
+        if name.is_empty() {
+            return Err(self.error(format!("missing {} name", what)));
+        }
+        Ok(name)
+    }
+    // resolves a 1-based (or negative, relative) index into a buffer of length len
+    fn index(&self, token: &str, len: usize, what: &str) -> Result<usize, ObjError> {
+        let i: i64 = token.parse().map_err(|_| self.error(format!("invalid {} index '{}'", what, token)))?;
+        let resolved = if i < 0 { len as i64 + i } else { i - 1 };
+        if i == 0 || resolved < 0 || resolved >= len as i64 {
+            return Err(self.error(format!("{} index {} out of range ({} defined)", what, i, len)));
+        }
+        Ok(resolved as usize)
+    }
+}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|error| ObjError::Io {file: path.to_path_buf(), error})
+}
+
+fn strip_comment(line: &str) -> &str {
+    line.split('#').next().unwrap_or("")
+}
+
+/// Reads the materials of an .mtl library.
+pub fn load_mtl(path: &Path) -> Result<Vec<MtlMaterial>, ObjError> {
+    let source = read(path)?;
+    let mut parser = Parser {file: path, line: 0};
+    let mut materials: Vec<MtlMaterial> = Vec::new();
+
+    for (n, line) in source.lines().enumerate() {
+        parser.line = n + 1;
+        let mut tokens = strip_comment(line).split_whitespace();
+        let Some(keyword) = tokens.next() else { continue };
+        if keyword == "newmtl" {
+            materials.push(MtlMaterial::construct(&parser.name(tokens, "material")?));
+            continue;
+        }
+        let current = match materials.last_mut() {
+            Some(m) => m,
+            None if matches!(keyword, "Kd" | "Ks" | "Ke" | "Ns" | "Ni" | "d" | "Tr" | "illum") =>
+                return Err(parser.error(format!("'{}' before any newmtl", keyword))),
+            None => continue
+        };
+        match keyword {
+            "Kd" => current.diffuse = parser.point(&mut tokens, "Kd")?,
+            "Ks" => current.specular = parser.point(&mut tokens, "Ks")?,
+            "Ke" => current.emission = parser.point(&mut tokens, "Ke")?,
+            "Ns" => current.shininess = parser.float(tokens.next(), "Ns")?,
+            "Ni" => match parser.float(tokens.next(), "Ni")? {
+                ior if ior > 0.0 => current.refractive_index = Some(ior),
+                ior => return Err(parser.error(format!("refractive index must be positive, got {}", ior)))
+            },
+            "d" => current.dissolve = parser.float(tokens.next(), "d")?,
+            "Tr" => current.dissolve = 1.0 - parser.float(tokens.next(), "Tr")?,
+            "illum" => {
+                let token = tokens.next().ok_or_else(|| parser.error("missing illum model".to_string()))?;
+                current.illum = token.parse().map_err(|_| parser.error(format!("invalid illum model '{}'", token)))?;
+            },
+            _ => {}  // texture maps and other statements are not supported
+        }
+    }
+    Ok(materials)
+}
+
+// faces collected for one (group, material) run, with vertices de-duplicated by their v/vt/vn triple
+struct MeshBuilder {
+    material: Option<String>,
+    lookup: HashMap<(usize, Option<usize>, Option<usize>), usize>,
+    corners: Vec<(usize, Option<usize>, Option<usize>)>,
+    indices: Vec<[usize; 3]>
+}
+
+impl MeshBuilder {
+    fn construct(material: Option<String>) -> MeshBuilder {
+        MeshBuilder {material, lookup: HashMap::new(), corners: Vec::new(), indices: Vec::new()}
+    }
+    fn corner(&mut self, corner: (usize, Option<usize>, Option<usize>)) -> usize {
+        *self.lookup.entry(corner).or_insert_with(|| {
+            self.corners.push(corner);
+            self.corners.len() - 1
+        })
+    }
+    fn build(self, positions: &[Point3], normals: &[Point3], uvs: &[(f64, f64)], materials: &[MtlMaterial]) -> Result<Mesh, RaytraceError> {
+        let vertices = self.corners.iter().map(|c| positions[c.0]).collect();
+        let mesh_normals = if self.corners.iter().all(|c| c.2.is_some()) {
+            Some(self.corners.iter().map(|c| normals[c.2.unwrap()]).collect())
+        } else {
+            None
+        };
+        let mesh_uvs = if self.corners.iter().all(|c| c.1.is_some()) {
+            Some(self.corners.iter().map(|c| uvs[c.1.unwrap()]).collect())
+        } else {
+            None
+        };
+        let material = self.material.as_ref()
+            .and_then(|name| materials.iter().find(|m| &m.name == name))
+            .map_or_else(|| Ok(Box::new(Diffuse::construct(0.8, 0.8, 0.8)?) as Box<dyn Material>), |m| m.to_material())?;
+        Mesh::construct(vertices, self.indices, mesh_normals, mesh_uvs, material)
+    }
+}
+
+/// Reads an .obj file, with its .mtl libraries, as one mesh per (group, material).
+pub fn load(path: &Path) -> Result<Vec<Mesh>, ObjError> {
+    let source = read(path)?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let mut parser = Parser {file: path, line: 0};
+
+    let mut positions: Vec<Point3> = Vec::new();
+    let mut normals: Vec<Point3> = Vec::new();
+    let mut uvs: Vec<(f64, f64)> = Vec::new();
+    let mut materials: Vec<MtlMaterial> = Vec::new();
+    let mut finished: Vec<MeshBuilder> = Vec::new();
+    let mut current = MeshBuilder::construct(None);
+
+    for (n, line) in source.lines().enumerate() {
+        parser.line = n + 1;
+        let mut tokens = strip_comment(line).split_whitespace();
+        let Some(keyword) = tokens.next() else { continue };
+        match keyword {
+            "v" => positions.push(parser.point(&mut tokens, "vertex")?),
+            "vn" => normals.push(parser.point(&mut tokens, "normal")?),
+            "vt" => {
+                let u = parser.float(tokens.next(), "texture coordinate")?;
+                let v = match tokens.next() {
+                    Some(token) => parser.float(Some(token), "texture coordinate")?,
+                    None => 0.0
+                };
+                uvs.push((u, v));
+            },
+            "f" => {
+                let mut polygon = Vec::new();
+                for token in tokens {
+                    let mut parts = token.split('/');
+                    let v = parser.index(parts.next().unwrap_or(""), positions.len(), "vertex")?;
+                    let vt = match parts.next() {
+                        Some("") | None => None,
+                        Some(t) => Some(parser.index(t, uvs.len(), "texture coordinate")?)
+                    };
+                    let vn = match parts.next() {
+                        Some("") | None => None,
+                        Some(t) => Some(parser.index(t, normals.len(), "normal")?)
+                    };
+                    if parts.next().is_some() {
+                        return Err(parser.error(format!("malformed face vertex '{}'", token)));
+                    }
+                    polygon.push(current.corner((v, vt, vn)));
+                }
+                if polygon.len() < 3 {
+                    return Err(parser.error(format!("face needs at least 3 vertices, got {}", polygon.len())));
+                }
+                for i in 1..polygon.len() - 1 {
+                    current.indices.push([polygon[0], polygon[i], polygon[i + 1]]);
+                }
+            },
+            "o" | "g" => {
+                let material = current.material.clone();
+                finished.push(std::mem::replace(&mut current, MeshBuilder::construct(material)));
+            },
+            "usemtl" => {
+                let name = parser.name(tokens, "material")?;  // unknown names fall back to the default material
+                finished.push(std::mem::replace(&mut current, MeshBuilder::construct(Some(name))));
+            },
+            "mtllib" => {
+                for file in tokens {
+                    materials.extend(load_mtl(&directory.join(file))?);
+                }
+            },
+            _ => {}  // smoothing groups, lines, points and free-form geometry are not supported
+        }
+    }
+    finished.push(current);
+
+    finished.into_iter()
+        .filter(|builder| !builder.indices.is_empty())
+        .map(|builder| builder.build(&positions, &normals, &uvs, &materials))
+        .collect::<Result<_, _>>()
+        .map_err(|error| ObjError::Invalid {file: path.to_path_buf(), error})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::Object;
+    use crate::utils::{Ray, Color3, MINIMUM, INFINITY};
+
+    // writes a file into a directory of this test run, for the loaders to read
+    fn write(name: &str, contents: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("raytracing-obj-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    const SQUARE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n";
+
+    #[test]
+    fn polygons_are_fan_triangulated() {
+        let path = write("fan.obj", &format!("{}v 0.5 1.5 0\nf 1 2 3 4\nf 1 2 3 5 4\n", SQUARE));
+        let meshes = load(&path).unwrap();
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(meshes[0].indices, [[0, 1, 2], [0, 2, 3], [0, 1, 2], [0, 2, 4], [0, 4, 3]]);
+        assert_eq!(meshes[0].vertices.len(), 5);  // shared corners are stored once
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_last_vertex() {
+        let source = format!("{}vt 0 0\nvt 1 0\nvt 1 1\nvn 0 0 1\nf -4/-3/-1 -3/-2/-1 -2/-1/-1\n", SQUARE);
+        let mesh = &load(&write("negative.obj", &source)).unwrap()[0];
+        assert_eq!(mesh.indices, [[0, 1, 2]]);
+        let (a, b, c) = mesh.triangle(0);
+        assert_eq!([(a.x, a.y), (b.x, b.y), (c.x, c.y)], [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]);
+        assert_eq!(mesh.uvs.as_deref(), Some(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)][..]));
+        assert!(mesh.normals.is_some());
+    }
+
+    #[test]
+    fn usemtl_picks_materials_from_mtllib() {
+        write("library.mtl", "newmtl red\nKd 1 0 0\n\nnewmtl lamp  # emits\nKd 0 0 0\nKe 2 3 4\n");
+        let source = format!("mtllib library.mtl\n{}usemtl red\nf 1 2 3\nusemtl lamp\nf 1 3 4\nusemtl missing\nf 1 2 4\n", SQUARE);
+        let meshes = load(&write("materials.obj", &source)).unwrap();
+        assert_eq!(meshes.len(), 3);
+
+        // straight down onto the square, through the triangle of each mesh
+        let down = Point3 {x: 0.0, y: 0.0, z: -1.0};
+        let emitted = |mesh: &Mesh, x: f64, y: f64| {
+            let hit = mesh.ray_hit(&Ray::construct(down, Point3 {x, y, z: 1.0}), (MINIMUM, INFINITY)).unwrap();
+            hit.material.emitted(&hit)
+        };
+        assert!(!meshes[0].is_emitter());
+        assert!(meshes[1].is_emitter());
+        assert_eq!(emitted(&meshes[1], 0.25, 0.75), Color3 {r: 2.0, g: 3.0, b: 4.0});
+        assert!(!meshes[2].is_emitter());  // unknown names fall back to the default material
+        assert_eq!(emitted(&meshes[0], 0.75, 0.25), Color3 {r: 0.0, g: 0.0, b: 0.0});
+    }
+
+    #[test]
+    fn parse_errors_give_the_file_and_line() {
+        let cases = [
+            ("bad_number.obj", format!("{}\nv 1 x 0\n", SQUARE), 6),
+            ("bad_index.obj", format!("{}f 1 2 5\n", SQUARE), 5),
+            ("zero_index.obj", format!("# comment\n{}f 0 1 2\n", SQUARE), 6),
+            ("short_face.obj", format!("{}f 1 2\n", SQUARE), 5)
+        ];
+        for (name, source, expected) in cases {
+            let path = write(name, &source);
+            match load(&path) {
+                Err(ObjError::Parse {file, line, ..}) => assert_eq!((file, line), (path, expected)),
+                other => panic!("{}: expected a parse error, got {:?}", name, other.map(|meshes| meshes.len()))
+            }
+        }
+
+        let library = write("bad.mtl", "newmtl a\nKd 1 1 1\nNi -1\n");
+        let obj = write("bad_library.obj", &format!("mtllib bad.mtl\n{}", SQUARE));
+        match load(&obj) {
+            Err(ObjError::Parse {file, line, ..}) => assert_eq!((file, line), (library, 3)),
+            other => panic!("expected a parse error in the library, got {:?}", other.map(|meshes| meshes.len()))
+        }
+    }
+}
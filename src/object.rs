@@ -1,146 +1,1030 @@
-use crate::utils::{Point3, Ray, Face, RayHit, Material};
+use std::path::Path;
+use std::sync::OnceLock;
+use rand::{Rng, RngCore};
+use crate::utils::{Point3, Color3, Ray, Face, RayHit, Material, ORIGIN, UNIT_X, UNIT_Y, UNIT_Z, MINIMUM, sample_cone};
+use crate::error::RaytraceError;
+use crate::obj::{self, ObjError};
+use crate::bvh::{Aabb, Bvh};
+use crate::light::Light;
+use crate::background::{Background, Gradient};
 
+/// The objects of a scene and the light around them; ray hits go through a bounding volume
+/// hierarchy over the objects.
 pub struct World {
-    pub objects: Vec<Box<dyn Object>>
+    objects: Vec<Box<dyn Object>>,
+    lights: Vec<Box<dyn Light>>,  // point, spot and directional lights, which rays cannot hit
+    pub background: Box<dyn Background>,  // what rays hitting nothing see, a blue sky by default
+    accelerator: OnceLock<Accelerator>  // built on the first hit, dropped whenever objects change
+}
+
+// objects with a bounding box go into the BVH, the rest (infinite planes) are tested one by one
+struct Accelerator {
+    bounded: Vec<usize>,
+    unbounded: Vec<usize>,
+    bvh: Bvh,
+    emitters: Vec<usize>  // emitting objects, for light sampling
 }
 
 impl World {
-    pub fn hit(&self, ray: &Ray, t_range: (f64, f64)) -> RayHit<'_> {
-        let mut closest: RayHit = RayHit::NoHit;
-        let mut min_t: f64 = f64::INFINITY;
+    pub fn construct(objects: Vec<Box<dyn Object>>) -> World {
+        World {objects, lights: Vec::new(), background: Box::new(Gradient::SKY), accelerator: OnceLock::new()}
+    }
+    pub fn background(&self, ray: &Ray) -> Color3 {
+        self.background.radiance(ray.A)
+    }
+    pub fn objects(&self) -> &[Box<dyn Object>] {
+        &self.objects
+    }
+    pub fn add(&mut self, object: Box<dyn Object>) {
+        self.objects.push(object);
+        self.accelerator = OnceLock::new();
+    }
+    pub fn lights(&self) -> &[Box<dyn Light>] {
+        &self.lights
+    }
+    pub fn add_light(&mut self, light: Box<dyn Light>) {
+        self.lights.push(light);
+    }
+    fn accelerator(&self) -> &Accelerator {
+        self.accelerator.get_or_init(|| {
+            let (bounded, unbounded): (Vec<usize>, Vec<usize>) = (0..self.objects.len())
+                .partition(|&i| self.objects[i].bounding_box().is_some());
+            let boxes: Vec<Aabb> = bounded.iter().map(|&i| self.objects[i].bounding_box().unwrap()).collect();
+            let emitters = (0..self.objects.len()).filter(|&i| self.objects[i].is_emitter()).collect();
+            Accelerator {bvh: Bvh::construct(&boxes), bounded, unbounded, emitters}
+        })
+    }
+    /// A direction from `origin` towards a point on an emitting object (where it is at `time`), or
+    /// into an emitting background, picked uniformly among them; None if nothing emits.
+    pub fn sample_emitter(&self, origin: Point3, time: f64, rng: &mut dyn RngCore) -> Option<Point3> {
+        let emitters = &self.accelerator().emitters;
+        let count = emitters.len() + self.background.is_emitter() as usize;
+        if count == 0 {
+            return None;
+        }
+        let chosen = rng.random_range(0..count);
+        let u = (rng.random(), rng.random());
+        match emitters.get(chosen) {
+            Some(&emitter) => self.objects[emitter].sample_towards(origin, time, u),
+            None => self.background.sample(u)
+        }
+    }
+    /// Solid angle density of [`World::sample_emitter`] giving the direction of `ray`.
+    pub fn emitter_pdf(&self, ray: &Ray) -> f64 {
+        let emitters = &self.accelerator().emitters;
+        let count = emitters.len() + self.background.is_emitter() as usize;
+        if count == 0 {
+            return 0.0;
+        }
+        let background = if self.background.is_emitter() { self.background.pdf(ray.A) } else { 0.0 };
+        (emitters.iter().map(|&i| self.objects[i].pdf_towards(ray)).sum::<f64>() + background) / count as f64
+    }
+    pub fn hit(&self, ray: &Ray, t_range: (f64, f64)) -> Option<RayHit<'_>> {
+        let accelerator = self.accelerator();
+        let mut closest = accelerator.bvh.traverse(ray, t_range, |i, range| {
+            self.objects[accelerator.bounded[i]].ray_hit(ray, range).map(|hit| (hit.t, hit))
+        }).map(|(i, t, hit)| (accelerator.bounded[i], t, hit));
+
+        for &i in &accelerator.unbounded {
+            let max_t = closest.as_ref().map_or(t_range.1, |c| c.1.next_up());
+            if let Some(hit) = self.objects[i].ray_hit(ray, (t_range.0, max_t))
+                && closest.as_ref().is_none_or(|c| hit.t < c.1 || (hit.t == c.1 && i < c.0)) {
+                closest = Some((i, hit.t, hit));
+            }
+        }
+        closest.map(|(_, _, hit)| hit)
+    }
+    /// Reference implementation of [`World::hit`], testing every object.
+    pub fn hit_linear(&self, ray: &Ray, t_range: (f64, f64)) -> Option<RayHit<'_>> {
+        let mut closest: Option<RayHit> = None;
 
         for object in &self.objects {
-            let hit: RayHit = object.ray_hit(ray, t_range);
-            closest = match hit {
-                RayHit::Hit {t, ..} => if t < min_t { min_t = t; hit } else { closest },
-                RayHit::NoHit => closest
+            if let Some(hit) = object.ray_hit(ray, t_range)
+                && closest.as_ref().is_none_or(|c| hit.t < c.t) {
+                closest = Some(hit);
             }
         }
         closest
     }
+    pub fn load_obj(&mut self, path: &Path) -> Result<(), ObjError> {
+        for mesh in obj::load(path)? {
+            self.add(Box::new(mesh));
+        }
+        Ok(())
+    }
+}
+
+// normal as a unit vector, if it has a direction at all
+fn unit_normal(normal: Point3) -> Result<Point3, RaytraceError> {
+    let unit = normal.unit_vector();
+    if !(unit.x.is_finite() && unit.y.is_finite() && unit.z.is_finite()) {
+        return Err(RaytraceError::ZeroNormal);
+    }
+    Ok(unit)
 }
 
-pub trait Object {
-    fn ray_hit(&self, ray: &Ray, t_range: (f64, f64)) -> RayHit<'_>;
+/// Anything a ray can hit.
+pub trait Object: Send + Sync {
+    fn ray_hit(&self, ray: &Ray, t_range: (f64, f64)) -> Option<RayHit<'_>>;
+    /// None for unbounded objects.
+    fn bounding_box(&self) -> Option<Aabb>;
+    fn is_emitter(&self) -> bool {
+        false
+    }
+    /// For light sampling: the direction from `origin` to a random point of the object where it
+    /// is at `time`, with `u` uniform in [0, 1)^2; None for objects that cannot be sampled.
+    fn sample_towards(&self, _origin: Point3, _time: f64, _u: (f64, f64)) -> Option<Point3> {
+        None
+    }
+    /// Solid angle density of [`Object::sample_towards`] giving the direction of `ray`, 0 if it misses.
+    fn pdf_towards(&self, _ray: &Ray) -> f64 {
+        0.0
+    }
 }
 
+// uniformly distributed over the triangle
+fn sample_triangle(a: Point3, b: Point3, c: Point3, u: (f64, f64)) -> Point3 {
+    let root = u.0.sqrt();
+    a*(1.0 - root) + b*(root*(1.0 - u.1)) + c*(root*u.1)
+}
+
+// converts a density over the area of a surface to one over the solid angle seen from origin
+fn area_to_solid_angle(pdf_area: f64, origin: Point3, point: Point3, normal: Point3) -> f64 {
+    let to_point = point - origin;
+    let distance_square = to_point.norm_square();
+    let cos = normal.dot(to_point).abs() / distance_square.sqrt();
+    if cos < MINIMUM {
+        return 0.0;
+    }
+    pdf_area * distance_square / cos
+}
+
+// the cone of directions from origin that a sphere covers, as its unit axis and the cosine of its
+// half angle; None when origin is inside, where the sphere covers every direction
+fn bounding_cone(center: Point3, radius: f64, origin: Point3) -> Option<(Point3, f64)> {
+    let axis = center - origin;
+    let distance_square = axis.norm_square();
+    if distance_square <= radius*radius {
+        return None;
+    }
+    Some((axis.unit_vector(), (1.0 - radius*radius / distance_square).sqrt()))
+}
+
+fn cone_pdf(cos_max: f64) -> f64 {
+    1.0 / (2.0 * std::f64::consts::PI * (1.0 - cos_max))
+}
+
+/// Sphere, by center and radius.
 pub struct Sphere {
     pub position: Point3,
     pub radius: f64,
     pub material: Box<dyn Material>
 }
 
+impl Sphere {
+    pub fn construct(position: Point3, radius: f64, material: Box<dyn Material>) -> Result<Sphere, RaytraceError> {
+        if !(radius.is_finite() && radius > 0.0) {
+            return Err(RaytraceError::InvalidRadius(radius));
+        }
+        Ok(Sphere {position, radius, material})
+    }
+}
+
 impl Object for Sphere {
-    fn ray_hit(&self, ray: &Ray, t_range: (f64, f64)) -> RayHit<'_> {
+    fn ray_hit(&self, ray: &Ray, t_range: (f64, f64)) -> Option<RayHit<'_>> {
         let b = ray.A.dot(self.position - ray.B) * (-2.0);
         let a = ray.A.norm_square();
         let c = (self.position - ray.B).norm_square() - self.radius*self.radius;
         let discriminant = b*b - 4.0*a*c;
 
         if discriminant < 0.0 {
-            return RayHit::NoHit;
-        }
-        let t = (-b - discriminant.powf(0.5)) / (2.0*a);
-        if t <= t_range.0 || t >= t_range.1 {
-            return RayHit::NoHit;
+            return None;
         }
+        // the near root, or the far one when the ray starts inside the sphere
+        let root = discriminant.powf(0.5);
+        let in_range = |t: f64| t > t_range.0 && t < t_range.1;
+        let t = [(-b - root) / (2.0*a), (-b + root) / (2.0*a)].into_iter().find(|&t| in_range(t))?;
         let point = ray.produce(t);
         let normal = (point - self.position).unit_vector();
+        let front = normal.dot(ray.A) < 0.0;
 
-        RayHit::Hit{
+        Some(RayHit {
             t,
             point,
-            face: if normal.dot(point) < 0.0 { Face::FrontFace } else { Face::BackFace },
-            normal: if normal.dot(point) < 0.0 { normal } else { -normal }, // always points opposite to ray
-            material: &self.material
+            face: if front { Face::FrontFace } else { Face::BackFace },
+            normal: if front { normal } else { -normal }, // always points opposite to ray
+            barycentric: None,
+            uv: None,
+            material: self.material.as_ref()
+        })
+    }
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Point3 {x: self.radius, y: self.radius, z: self.radius};
+        Some(Aabb::construct(self.position - r, self.position + r))
+    }
+    fn is_emitter(&self) -> bool {
+        self.material.is_emitter()
+    }
+    fn sample_towards(&self, origin: Point3, _time: f64, u: (f64, f64)) -> Option<Point3> {
+        // uniformly over the cone of directions the sphere covers
+        let (axis, cos_max) = bounding_cone(self.position, self.radius, origin)?;
+        Some(sample_cone(axis, cos_max, u))
+    }
+    fn pdf_towards(&self, ray: &Ray) -> f64 {
+        match bounding_cone(self.position, self.radius, ray.B) {
+            Some((axis, cos_max)) if ray.A.dot(axis) >= cos_max => cone_pdf(cos_max),
+            _ => 0.0
         }
     }
 }
 
+/// Infinite plane through a point, in any orientation.
 pub struct Plane {
-    pub mx: f64,
-    pub my: f64,
-    pub b: f64,
-    pub normal: Point3  // which way the plane is facing
+    pub point: Point3,  // any point on the plane
+    pub normal: Point3, // unit normal, which way the plane is facing
+    pub material: Box<dyn Material>
 }
 
 impl Plane {
-    pub fn construct(mx: f64, my: f64, b: f64, internal_pt: Point3) -> Plane {
-        let along_x = Point3 {x: 1.0, y: 0.0, z: mx};
-        let along_y = Point3 {x: 0.0, y: 1.0, z: my};
-        let normal = along_x.cross(along_y);
-        
-        // panic if internal_pt is on the plane
-        let z = internal_pt.x * mx + internal_pt.y * my + b;
-        if (internal_pt.z - z).abs() < 0.00001 {
-            panic!("Point used to construct normal to plane cannot be on the plane!\nPoint {:#?}", internal_pt);
-        }
-
-        Plane {
-            mx, my, b, 
-            normal: if internal_pt.dot(normal) > 0.0 { -normal } else { normal } // TODO: check if normal is correct
-        }
+    pub fn construct(point: Point3, normal: Point3, material: Box<dyn Material>) -> Result<Plane, RaytraceError> {
+        Ok(Plane {point, normal: unit_normal(normal)?, material})
+    }
+    pub fn from_points(a: Point3, b: Point3, c: Point3, material: Box<dyn Material>) -> Result<Plane, RaytraceError> {
+        // facing by the right hand rule on a -> b -> c, like Triangle
+        Self::construct(a, (b - a).cross(c - a), material)
     }
     pub fn intersect(&self, ray: &Ray) -> Option<f64> {
-        // Equation of plane: z = mx*x + my*y + b 
-        // Also equation of plane: z = m . [x, y, 0] + b  where m = [mx, my, -1]
-        // => Az*t + Bz = mx*(Ax*t+Bx) + my*(Ay*t+By) + b
+        // Equation of plane: (p - point) . normal = 0
+        // => (A*t + B - point) . normal = 0
         // solve for t.
-        let to_mul: Point3 = Point3 {x: self.mx, y: self.my, z: -1.0};
-        let numerator = ray.B.dot(to_mul) + self.b;
-        let denominator = ray.A.dot(-to_mul);
-        let t = numerator / denominator;
-
-        if self.is_on_plane(ray.produce(t)) {
-            Some(t)
-        } else {
-            None
+        let denominator = ray.A.dot(self.normal);
+        if denominator.abs() < MINIMUM {
+            return None;  // ray is parallel to the plane
         }
+        Some((self.point - ray.B).dot(self.normal) / denominator)
     }
     pub fn is_on_plane(&self, pt: Point3) -> bool {
-        let z = pt.x * self.mx + pt.y * self.my + self.b;
-        if (pt.z - z).abs() < 0.00001 {
-            true
-        } else {
-            false
+        (pt - self.point).dot(self.normal).abs() < 0.00001
+    }
+}
+
+impl Object for Plane {
+    fn ray_hit(&self, ray: &Ray, t_range: (f64, f64)) -> Option<RayHit<'_>> {
+        let t = self.intersect(ray)?;
+        if t <= t_range.0 || t >= t_range.1 {
+            return None;
         }
+        let front = self.normal.dot(ray.A) < 0.0;
+
+        Some(RayHit {
+            t,
+            point: ray.produce(t),
+            normal: if front { self.normal } else { -self.normal },
+            face: if front { Face::FrontFace } else { Face::BackFace },
+            barycentric: None,
+            uv: None,
+            material: self.material.as_ref()
+        })
+    }
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
     }
 }
 
+/// Single triangle; use [`Mesh`] for many of them.
 pub struct Triangle {
-    // pub A: Point3,
-    // pub B: Point3,
-    // pub C: Point3,
-    pub material: Box<dyn Material>,
-    pub plane: Plane
+    pub a: Point3,
+    pub b: Point3,
+    pub c: Point3,
+    pub normal: Point3,  // unit normal, by the right hand rule on a -> b -> c
+    pub material: Box<dyn Material>
 }
 
 impl Triangle {
-    pub fn construct(A: Point3, B: Point3, C: Point3, material: Box<dyn Material>) -> Triangle {
-        Triangle {
-            material,
-            plane: Plane::construct(0.0, 0.0, 0.0, Point3 {x: 0.0, y: 0.0, z: 0.0}) // TODO:
+    pub fn construct(a: Point3, b: Point3, c: Point3, material: Box<dyn Material>) -> Result<Triangle, RaytraceError> {
+        Ok(Triangle {
+            a, b, c,
+            normal: unit_normal((b - a).cross(c - a))?,
+            material
+        })
+    }
+    pub fn intersect(ray: &Ray, a: Point3, b: Point3, c: Point3) -> Option<(f64, f64, f64)> {
+        // Moller-Trumbore: solve B + A*t = a + (b - a)*u + (c - a)*v for (t, u, v)
+        let edge_ab = b - a;
+        let edge_ac = c - a;
+        let p = ray.A.cross(edge_ac);
+        let determinant = edge_ab.dot(p);
+        // relative to the edges, so that the size of the triangle doesn't matter
+        if determinant.abs() < MINIMUM * edge_ab.norm() * edge_ac.norm() {
+            return None;  // ray is parallel to the triangle
+        }
+        let inverse = 1.0 / determinant;
+        let s = ray.B - a;
+        let u = s.dot(p) * inverse;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(edge_ab);
+        let v = ray.A.dot(q) * inverse;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
         }
+        Some((edge_ac.dot(q) * inverse, u, v))
     }
 }
 
 impl Object for Triangle {
-    fn ray_hit(&self, ray: &Ray, t_range: (f64, f64)) -> RayHit<'_> {
-        let Some(t) = self.plane.intersect(ray) else {return RayHit::NoHit};
+    fn ray_hit(&self, ray: &Ray, t_range: (f64, f64)) -> Option<RayHit<'_>> {
+        let (t, u, v) = Triangle::intersect(ray, self.a, self.b, self.c)?;
         if t <= t_range.0 || t >= t_range.1 {
-            return RayHit::NoHit;
+            return None;
         }
-        let normal = self.plane.normal;
-        let point = ray.produce(t);
+        let front = self.normal.dot(ray.A) < 0.0;
 
-        RayHit::Hit {
+        Some(RayHit {
             t,
-            point,
-            normal: if normal.dot(point) < 0.0 { normal } else { -normal },
-            face: if normal.dot(point) < 0.0 { Face::FrontFace } else { Face::BackFace },
-            material: &self.material
+            point: ray.produce(t),
+            normal: if front { self.normal } else { -self.normal },
+            face: if front { Face::FrontFace } else { Face::BackFace },
+            barycentric: Some((u, v)),
+            uv: None,
+            material: self.material.as_ref()
+        })
+    }
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::construct(self.a, self.b).grow(self.c))
+    }
+    fn is_emitter(&self) -> bool {
+        self.material.is_emitter()
+    }
+    fn sample_towards(&self, origin: Point3, _time: f64, u: (f64, f64)) -> Option<Point3> {
+        Some((sample_triangle(self.a, self.b, self.c, u) - origin).unit_vector())
+    }
+    fn pdf_towards(&self, ray: &Ray) -> f64 {
+        match Triangle::intersect(ray, self.a, self.b, self.c) {
+            Some((t, ..)) if t > MINIMUM => {
+                let area = (self.b - self.a).cross(self.c - self.a).norm() / 2.0;
+                area_to_solid_angle(1.0 / area, ray.B, ray.produce(t), self.normal)
+            },
+            _ => 0.0
+        }
+    }
+}
+
+/// How a quad or disk picks the points it is sampled at, as a light.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LightSampling {
+    Area,        // uniformly over its surface
+    BoundingCone // uniformly over the cone of directions towards its bounding sphere, those that miss
+                 // the shape giving no light; for small lights seen face on from far away
+}
+
+impl LightSampling {
+    pub fn from_name(name: &str) -> Option<LightSampling> {
+        match name {
+            "area" => Some(LightSampling::Area),
+            "bounding_cone" => Some(LightSampling::BoundingCone),
+            _ => None
+        }
+    }
+}
+
+// light sampling for flat shapes, by their bounding sphere and area; points on the shape itself
+// come from the shape
+struct Flat {
+    sampling: LightSampling,
+    center: Point3,
+    radius: f64,
+    area: f64
+}
+
+impl Flat {
+    fn sample_towards(&self, origin: Point3, u: (f64, f64), point_at: impl Fn((f64, f64)) -> Point3) -> Point3 {
+        // from inside the bounding sphere there is no cone, and it falls back to area sampling
+        if self.sampling == LightSampling::BoundingCone && let Some((axis, cos_max)) = bounding_cone(self.center, self.radius, origin) {
+            return sample_cone(axis, cos_max, u);
+        }
+        (point_at(u) - origin).unit_vector()
+    }
+    // hit is the distance along ray to the shape, if it hits
+    fn pdf_towards(&self, ray: &Ray, hit: Option<f64>, normal: Point3) -> f64 {
+        if self.sampling == LightSampling::BoundingCone && let Some((axis, cos_max)) = bounding_cone(self.center, self.radius, ray.B) {
+            return if ray.A.dot(axis) >= cos_max { cone_pdf(cos_max) } else { 0.0 };
+        }
+        match hit {
+            Some(t) if t > MINIMUM => area_to_solid_angle(1.0 / self.area, ray.B, ray.produce(t), normal),
+            _ => 0.0
+        }
+    }
+}
+
+// where a ray meets the plane through point with a unit normal
+fn plane_distance(ray: &Ray, point: Point3, normal: Point3) -> Option<f64> {
+    let denominator = normal.dot(ray.A);
+    if denominator.abs() < MINIMUM {
+        return None;  // ray is parallel to the plane
+    }
+    Some(normal.dot(point - ray.B) / denominator)
+}
+
+/// Parallelogram from a corner along two edges; a rectangle when they are perpendicular.
+pub struct Quad {
+    pub corner: Point3,
+    pub u: Point3,
+    pub v: Point3,
+    pub normal: Point3,  // unit, by the right hand rule on u -> v
+    pub sampling: LightSampling,
+    pub material: Box<dyn Material>
+}
+
+impl Quad {
+    pub fn construct(corner: Point3, u: Point3, v: Point3, material: Box<dyn Material>) -> Result<Quad, RaytraceError> {
+        Ok(Quad {corner, u, v, normal: unit_normal(u.cross(v))?, sampling: LightSampling::Area, material})
+    }
+    // the distance along ray and the position (alpha, beta) along u and v, in [0, 1]
+    fn intersect(&self, ray: &Ray) -> Option<(f64, f64, f64)> {
+        let t = plane_distance(ray, self.corner, self.normal)?;
+        let relative = ray.produce(t) - self.corner;
+        let cross = self.u.cross(self.v);
+        let w = cross / cross.norm_square();
+        let alpha = w.dot(relative.cross(self.v));
+        let beta = w.dot(self.u.cross(relative));
+        ((0.0..=1.0).contains(&alpha) && (0.0..=1.0).contains(&beta)).then_some((t, alpha, beta))
+    }
+    fn flat(&self) -> Flat {
+        Flat {
+            sampling: self.sampling,
+            center: self.corner + (self.u + self.v) / 2.0,
+            radius: (self.u + self.v).norm().max((self.u - self.v).norm()) / 2.0,
+            area: self.u.cross(self.v).norm()
+        }
+    }
+}
+
+impl Object for Quad {
+    fn ray_hit(&self, ray: &Ray, t_range: (f64, f64)) -> Option<RayHit<'_>> {
+        let (t, alpha, beta) = self.intersect(ray)?;
+        if t <= t_range.0 || t >= t_range.1 {
+            return None;
+        }
+        let front = self.normal.dot(ray.A) < 0.0;
+
+        Some(RayHit {
+            t,
+            point: ray.produce(t),
+            normal: if front { self.normal } else { -self.normal },
+            face: if front { Face::FrontFace } else { Face::BackFace },
+            barycentric: None,
+            uv: Some((alpha, beta)),
+            material: self.material.as_ref()
+        })
+    }
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::construct(self.corner, self.corner + self.u + self.v).grow(self.corner + self.u).grow(self.corner + self.v))
+    }
+    fn is_emitter(&self) -> bool {
+        self.material.is_emitter()
+    }
+    fn sample_towards(&self, origin: Point3, _time: f64, u: (f64, f64)) -> Option<Point3> {
+        Some(self.flat().sample_towards(origin, u, |(a, b)| self.corner + self.u*a + self.v*b))
+    }
+    fn pdf_towards(&self, ray: &Ray) -> f64 {
+        self.flat().pdf_towards(ray, self.intersect(ray).map(|(t, ..)| t), self.normal)
+    }
+}
+
+/// Flat disk by center, normal and radius.
+pub struct Disk {
+    pub center: Point3,
+    pub normal: Point3,  // unit
+    pub radius: f64,
+    pub sampling: LightSampling,
+    pub material: Box<dyn Material>
+}
+
+impl Disk {
+    pub fn construct(center: Point3, normal: Point3, radius: f64, material: Box<dyn Material>) -> Result<Disk, RaytraceError> {
+        if !(radius.is_finite() && radius > 0.0) {
+            return Err(RaytraceError::InvalidRadius(radius));
         }
+        Ok(Disk {center, normal: unit_normal(normal)?, radius, sampling: LightSampling::Area, material})
+    }
+    fn intersect(&self, ray: &Ray) -> Option<f64> {
+        let t = plane_distance(ray, self.center, self.normal)?;
+        ((ray.produce(t) - self.center).norm_square() <= self.radius*self.radius).then_some(t)
+    }
+    fn flat(&self) -> Flat {
+        Flat {sampling: self.sampling, center: self.center, radius: self.radius, area: std::f64::consts::PI * self.radius*self.radius}
     }
 }
 
+impl Object for Disk {
+    fn ray_hit(&self, ray: &Ray, t_range: (f64, f64)) -> Option<RayHit<'_>> {
+        let t = self.intersect(ray)?;
+        if t <= t_range.0 || t >= t_range.1 {
+            return None;
+        }
+        let front = self.normal.dot(ray.A) < 0.0;
+
+        Some(RayHit {
+            t,
+            point: ray.produce(t),
+            normal: if front { self.normal } else { -self.normal },
+            face: if front { Face::FrontFace } else { Face::BackFace },
+            barycentric: None,
+            uv: None,
+            material: self.material.as_ref()
+        })
+    }
+    fn bounding_box(&self) -> Option<Aabb> {
+        // how far the rim reaches along each axis
+        let n = self.normal;
+        let extent = Point3 {
+            x: (1.0 - n.x*n.x).max(0.0).sqrt(),
+            y: (1.0 - n.y*n.y).max(0.0).sqrt(),
+            z: (1.0 - n.z*n.z).max(0.0).sqrt()
+        } * self.radius;
+        Some(Aabb::construct(self.center - extent, self.center + extent))
+    }
+    fn is_emitter(&self) -> bool {
+        self.material.is_emitter()
+    }
+    fn sample_towards(&self, origin: Point3, _time: f64, u: (f64, f64)) -> Option<Point3> {
+        let helper = if self.normal.x.abs() > 0.9 { UNIT_Y } else { UNIT_X };
+        let tangent = self.normal.cross(helper).unit_vector();
+        let bitangent = self.normal.cross(tangent);
+        Some(self.flat().sample_towards(origin, u, |(a, b)| {
+            let (r, phi) = (self.radius * a.sqrt(), 2.0 * std::f64::consts::PI * b);
+            self.center + tangent*(r*phi.cos()) + bitangent*(r*phi.sin())
+        }))
+    }
+    fn pdf_towards(&self, ray: &Ray) -> f64 {
+        self.flat().pdf_towards(ray, self.intersect(ray), self.normal)
+    }
+}
+
+/// Triangle mesh sharing vertex buffers and one material.
+pub struct Mesh {
+    pub vertices: Vec<Point3>,
+    pub indices: Vec<[usize; 3]>,  // one triangle per entry, indexing into vertices (and normals, uvs)
+    pub normals: Option<Vec<Point3>>,
+    pub uvs: Option<Vec<(f64, f64)>>,
+    pub material: Box<dyn Material>,
+    bvh: Bvh,  // over the triangles, built once in construct
+    areas: Vec<f64>  // running total of the triangle areas, for light sampling
+}
+
+impl Mesh {
+    pub fn construct(vertices: Vec<Point3>, indices: Vec<[usize; 3]>, normals: Option<Vec<Point3>>, uvs: Option<Vec<(f64, f64)>>, material: Box<dyn Material>) -> Result<Mesh, RaytraceError> {
+        if let Some(&index) = indices.iter().flatten().find(|&&i| i >= vertices.len()) {
+            return Err(RaytraceError::MeshIndexOutOfRange {index, vertices: vertices.len()});
+        }
+        if let Some(n) = &normals && n.len() != vertices.len() {
+            return Err(RaytraceError::MeshAttributeLength {attribute: "normals", expected: vertices.len(), found: n.len()});
+        }
+        if let Some(uv) = &uvs && uv.len() != vertices.len() {
+            return Err(RaytraceError::MeshAttributeLength {attribute: "uvs", expected: vertices.len(), found: uv.len()});
+        }
+        let boxes: Vec<Aabb> = indices.iter()
+            .map(|&[a, b, c]| Aabb::construct(vertices[a], vertices[b]).grow(vertices[c]))
+            .collect();
+        let areas = indices.iter()
+            .scan(0.0, |total, &[a, b, c]| {
+                *total += (vertices[b] - vertices[a]).cross(vertices[c] - vertices[a]).norm() / 2.0;
+                Some(*total)
+            })
+            .collect();
+        Ok(Mesh {bvh: Bvh::construct(&boxes), areas, vertices, indices, normals, uvs, material})
+    }
+    pub fn triangle(&self, i: usize) -> (Point3, Point3, Point3) {
+        let [a, b, c] = self.indices[i];
+        (self.vertices[a], self.vertices[b], self.vertices[c])
+    }
+    fn hit_triangle(&self, i: usize, ray: &Ray, t: f64, u: f64, v: f64) -> RayHit<'_> {
+        let [ia, ib, ic] = self.indices[i];
+        let (a, b, c) = self.triangle(i);
+        let w = 1.0 - u - v;
+        let geometric = (b - a).cross(c - a).unit_vector();
+        let front = geometric.dot(ray.A) < 0.0;
+        let normal = match &self.normals {
+            Some(n) => {  // shading normal, kept on the same side as the geometric one
+                let interpolated = (n[ia]*w + n[ib]*u + n[ic]*v).unit_vector();
+                if interpolated.dot(geometric) < 0.0 { -interpolated } else { interpolated }
+            },
+            None => geometric
+        };
+        let uv = self.uvs.as_ref().map(|uv| (
+            uv[ia].0*w + uv[ib].0*u + uv[ic].0*v,
+            uv[ia].1*w + uv[ib].1*u + uv[ic].1*v
+        ));
+
+        RayHit {
+            t,
+            point: ray.produce(t),
+            normal: if front { normal } else { -normal },
+            face: if front { Face::FrontFace } else { Face::BackFace },
+            barycentric: Some((u, v)),
+            uv,
+            material: self.material.as_ref()
+        }
+    }
+}
 
+impl Object for Mesh {
+    fn ray_hit(&self, ray: &Ray, t_range: (f64, f64)) -> Option<RayHit<'_>> {
+        let closest = self.bvh.traverse(ray, t_range, |i, range| {
+            let (a, b, c) = self.triangle(i);
+            let (t, u, v) = Triangle::intersect(ray, a, b, c)?;
+            (t > range.0 && t < range.1).then_some((t, (u, v)))
+        });
+        closest.map(|(i, t, (u, v))| self.hit_triangle(i, ray, t, u, v))
+    }
+    fn bounding_box(&self) -> Option<Aabb> {
+        (!self.indices.is_empty()).then(|| self.bvh.bounds())
+    }
+    fn is_emitter(&self) -> bool {
+        self.material.is_emitter()
+    }
+    fn sample_towards(&self, origin: Point3, _time: f64, u: (f64, f64)) -> Option<Point3> {
+        // a triangle by its share of the area, then a point on it, reusing u.0 for both
+        let total = *self.areas.last()?;
+        let target = u.0 * total;
+        let i = self.areas.partition_point(|&area| area <= target).min(self.areas.len() - 1);
+        let start = if i == 0 { 0.0 } else { self.areas[i - 1] };
+        let v = ((target - start) / (self.areas[i] - start)).clamp(0.0, 1.0);
+        let (a, b, c) = self.triangle(i);
+        Some((sample_triangle(a, b, c, (v, u.1)) - origin).unit_vector())
+    }
+    fn pdf_towards(&self, ray: &Ray) -> f64 {
+        // every triangle along the ray could have been sampled, not only the closest; the
+        // closure never reports a hit, so traverse keeps the whole range and visits them all
+        let Some(&total) = self.areas.last() else { return 0.0 };
+        let mut pdf = 0.0;
+        self.bvh.traverse(ray, (MINIMUM, f64::INFINITY), |i, range| {
+            let (a, b, c) = self.triangle(i);
+            if let Some((t, ..)) = Triangle::intersect(ray, a, b, c) && t > range.0 && t < range.1 {
+                pdf += area_to_solid_angle(1.0 / total, ray.B, ray.produce(t), (b - a).cross(c - a).unit_vector());
+            }
+            None::<(f64, ())>
+        });
+        pdf
+    }
+}
+
+/// An object moving rigidly while the camera shutter is open: translated, and spun about an
+/// axis through `pivot`, linearly from time 0 to time 1 and standing still outside of that.
+pub struct Moving {
+    pub object: Box<dyn Object>,
+    pub translation: Point3,  // offset at time 1, none at time 0
+    pub pivot: Point3,
+    pub axis: Point3,  // unit
+    pub angle: f64     // radians at time 1, counterclockwise about axis
+}
+
+impl Moving {
+    pub fn construct(object: Box<dyn Object>, translation: Point3, pivot: Point3, axis: Point3, angle: f64) -> Result<Moving, RaytraceError> {
+        let axis = axis.unit_vector();
+        if !(axis.x.is_finite() && axis.y.is_finite() && axis.z.is_finite()) {
+            return Err(RaytraceError::ZeroAxis);
+        }
+        Ok(Moving {object, translation, pivot, axis, angle})
+    }
+    pub fn translate(object: Box<dyn Object>, translation: Point3) -> Moving {
+        Moving {object, translation, pivot: ORIGIN, axis: UNIT_Z, angle: 0.0}
+    }
+    // offset and rotation angle at a time
+    fn at(&self, time: f64) -> (Point3, f64) {
+        let s = time.clamp(0.0, 1.0);
+        (self.translation * s, self.angle * s)
+    }
+    // a point into the object's own frame, where it is at time 0
+    fn to_local(&self, point: Point3, (offset, angle): (Point3, f64)) -> Point3 {
+        (point - offset - self.pivot).rotate(self.axis, -angle) + self.pivot
+    }
+    // a ray into the object's own frame; t is the same in both
+    fn local_ray(&self, ray: &Ray) -> Ray {
+        let (offset, angle) = self.at(ray.time);
+        Ray {A: ray.A.rotate(self.axis, -angle), B: self.to_local(ray.B, (offset, angle)), time: ray.time}
+    }
+}
+
+impl Sphere {
+    /// A sphere going from one center at time 0 to the other at time 1.
+    pub fn moving(start: Point3, end: Point3, radius: f64, material: Box<dyn Material>) -> Result<Moving, RaytraceError> {
+        Ok(Moving::translate(Box::new(Sphere::construct(start, radius, material)?), end - start))
+    }
+}
+
+impl Object for Moving {
+    fn ray_hit(&self, ray: &Ray, t_range: (f64, f64)) -> Option<RayHit<'_>> {
+        let mut hit = self.object.ray_hit(&self.local_ray(ray), t_range)?;
+        hit.point = ray.produce(hit.t);
+        hit.normal = hit.normal.rotate(self.axis, self.at(ray.time).1);
+        Some(hit)
+    }
+    fn bounding_box(&self) -> Option<Aabb> {
+        let still = self.object.bounding_box()?;
+        let swept = if self.angle == 0.0 { still } else {
+            // every corner of the box stays on its circle about the axis
+            let mut swept = Aabb::EMPTY;
+            for i in 0..8 {
+                let corner = Point3 {
+                    x: if i & 1 == 0 { still.min.x } else { still.max.x },
+                    y: if i & 2 == 0 { still.min.y } else { still.max.y },
+                    z: if i & 4 == 0 { still.min.z } else { still.max.z }
+                };
+                let center = self.pivot + self.axis * self.axis.dot(corner - self.pivot);
+                let radius = (corner - center).norm();
+                let a = self.axis;
+                let extent = Point3 {
+                    x: radius * (1.0 - a.x*a.x).max(0.0).sqrt(),
+                    y: radius * (1.0 - a.y*a.y).max(0.0).sqrt(),
+                    z: radius * (1.0 - a.z*a.z).max(0.0).sqrt()
+                };
+                swept = swept.union(Aabb::construct(center - extent, center + extent));
+            }
+            swept
+        };
+        let moved = Aabb::construct(swept.min + self.translation, swept.max + self.translation);
+        Some(swept.union(moved))
+    }
+    fn is_emitter(&self) -> bool {
+        self.object.is_emitter()
+    }
+    fn sample_towards(&self, origin: Point3, time: f64, u: (f64, f64)) -> Option<Point3> {
+        let at = self.at(time);
+        let direction = self.object.sample_towards(self.to_local(origin, at), time, u)?;
+        Some(direction.rotate(self.axis, at.1))
+    }
+    fn pdf_towards(&self, ray: &Ray) -> f64 {
+        self.object.pdf_towards(&self.local_ray(ray))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+    use crate::material::Diffuse;
+    use crate::random::Pcg32;
+    use crate::utils::INFINITY;
+
+    fn material() -> Box<dyn Material> {
+        Box::new(Diffuse::construct(0.5, 0.5, 0.5).unwrap())
+    }
+
+    fn random_point(rng: &mut Pcg32, extent: f64) -> Point3 {
+        Point3 {x: rng.random_range(-extent..extent), y: rng.random_range(-extent..extent), z: rng.random_range(-extent..extent)}
+    }
+
+    // spheres, triangles, meshes and a few planes, some of them twice so that hits tie
+    fn random_world(rng: &mut Pcg32) -> World {
+        let mut objects: Vec<Box<dyn Object>> = Vec::new();
+        for _ in 0..60 {
+            let copies = if rng.random_bool(0.2) { 2 } else { 1 };
+            match rng.random_range(0..10) {
+                0..=3 => {
+                    let (center, radius) = (random_point(rng, 10.0), rng.random_range(0.2..2.0));
+                    for _ in 0..copies {
+                        objects.push(Box::new(Sphere::construct(center, radius, material()).unwrap()));
+                    }
+                },
+                4..=6 => {
+                    let center = random_point(rng, 10.0);
+                    let (a, b, c) = (center + random_point(rng, 2.0), center + random_point(rng, 2.0), center + random_point(rng, 2.0));
+                    for _ in 0..copies {
+                        objects.push(Box::new(Triangle::construct(a, b, c, material()).unwrap()));
+                    }
+                },
+                7..=8 => {
+                    let center = random_point(rng, 10.0);
+                    let vertices: Vec<Point3> = (0..8).map(|_| center + random_point(rng, 2.0)).collect();
+                    let indices: Vec<[usize; 3]> = (0..6).map(|_| [rng.random_range(0..8), rng.random_range(0..8), rng.random_range(0..8)]).collect();
+                    for _ in 0..copies {
+                        objects.push(Box::new(Mesh::construct(vertices.clone(), indices.clone(), None, None, material()).unwrap()));
+                    }
+                },
+                _ => {
+                    let (point, normal) = (random_point(rng, 20.0), random_point(rng, 1.0));
+                    for _ in 0..copies {
+                        objects.push(Box::new(Plane::construct(point, normal, material()).unwrap()));
+                    }
+                }
+            }
+        }
+        World::construct(objects)
+    }
+
+    fn assert_same_hit(ray: &Ray, world: &World) {
+        let (fast, slow) = (world.hit(ray, (MINIMUM, INFINITY)), world.hit_linear(ray, (MINIMUM, INFINITY)));
+        match (fast, slow) {
+            (Some(fast), Some(slow)) => {
+                assert_eq!(fast.t, slow.t, "t differs for {:?}", ray);
+                let (p, q) = (fast.point, slow.point);
+                assert_eq!((p.x, p.y, p.z), (q.x, q.y, q.z), "point differs for {:?}", ray);
+                assert!(std::ptr::addr_eq(fast.material, slow.material), "material differs for {:?}", ray);
+            },
+            (None, None) => {},
+            (fast, slow) => panic!("hit {} but hit_linear {} for {:?}", fast.is_some(), slow.is_some(), ray)
+        }
+    }
+
+    #[test]
+    fn hit_matches_hit_linear() {
+        let mut rng = Pcg32::construct(7, 0);
+        for _ in 0..20 {
+            let world = random_world(&mut rng);
+            for _ in 0..500 {
+                let ray = Ray::construct(random_point(&mut rng, 1.0), random_point(&mut rng, 15.0));
+                assert_same_hit(&ray, &world);
+            }
+        }
+    }
+
+    // straight down onto the plane z = 0 at (x, y)
+    fn down_at(x: f64, y: f64) -> Ray {
+        Ray::construct(-UNIT_Z, Point3 {x, y, z: 1.0})
+    }
+
+    #[test]
+    fn triangle_hits_on_edges_and_misses_outside() {
+        let (a, b, c) = (ORIGIN, UNIT_X, UNIT_Y);
+        assert!(Triangle::intersect(&down_at(0.5, 0.0), a, b, c).is_some());   // on the edge ab
+        assert!(Triangle::intersect(&down_at(0.5, 0.5), a, b, c).is_some());   // on the edge bc
+        assert!(Triangle::intersect(&down_at(0.0, 0.0), a, b, c).is_some());   // on a corner
+        assert!(Triangle::intersect(&down_at(0.5, -1e-9), a, b, c).is_none());
+        assert!(Triangle::intersect(&down_at(-1e-9, 0.5), a, b, c).is_none());
+        assert!(Triangle::intersect(&down_at(0.5, 0.5 + 1e-9), a, b, c).is_none());
+        // along the plane of the triangle
+        assert!(Triangle::intersect(&Ray::construct(UNIT_X, Point3 {x: -1.0, y: 0.2, z: 0.0}), a, b, c).is_none());
+    }
+
+    #[test]
+    fn triangle_hits_give_barycentric_coordinates() {
+        let triangle = Triangle::construct(ORIGIN, UNIT_X * 2.0, UNIT_Y * 4.0, material()).unwrap();
+        let hit = triangle.ray_hit(&down_at(0.5, 2.0), (MINIMUM, INFINITY)).unwrap();
+        let (u, v) = hit.barycentric.unwrap();
+        assert!((u - 0.25).abs() < 1e-12 && (v - 0.5).abs() < 1e-12, "{:?}", (u, v));
+        assert!((hit.t - 1.0).abs() < 1e-12);
+        assert!(matches!(hit.face, Face::FrontFace) && hit.normal.z == 1.0);
+    }
+
+    #[test]
+    fn mesh_hits_interpolate_uvs() {
+        let uvs = vec![(0.1, 0.2), (0.9, 0.2), (0.1, 0.8)];
+        let mesh = Mesh::construct(vec![ORIGIN, UNIT_X, UNIT_Y], vec![[0, 1, 2]], None, Some(uvs), material()).unwrap();
+        let hit = mesh.ray_hit(&down_at(0.25, 0.5), (MINIMUM, INFINITY)).unwrap();
+        let (s, t) = hit.uv.unwrap();
+        // 0.25 of the way to the second vertex, 0.5 to the third
+        assert!((s - (0.1 + 0.8*0.25)).abs() < 1e-12 && (t - (0.2 + 0.6*0.5)).abs() < 1e-12, "{:?}", (s, t));
+    }
+
+    #[test]
+    fn tiny_triangles_are_hit() {
+        for size in [1e-3, 1e-4, 1e-7] {
+            let (a, b, c) = (Point3 {x: 5.0, y: 5.0, z: 0.0}, Point3 {x: 5.0 + size, y: 5.0, z: 0.0}, Point3 {x: 5.0, y: 5.0 + size, z: 0.0});
+            let (t, u, v) = Triangle::intersect(&down_at(5.0 + size/4.0, 5.0 + size/4.0), a, b, c).expect("tiny triangle missed");
+            assert!((t - 1.0).abs() < 1e-9 && (u - 0.25).abs() < 1e-6 && (v - 0.25).abs() < 1e-6, "{:?}", (t, u, v));
+        }
+    }
+
+    #[test]
+    fn planes_are_hit_in_every_orientation() {
+        let point = Point3 {x: 1.0, y: 2.0, z: 3.0};
+        for normal in [UNIT_X, UNIT_Z, Point3 {x: 0.0, y: 1.0, z: 1.0}, Point3 {x: -1.0, y: 2.0, z: -3.0}] {
+            let plane = Plane::construct(point, normal * 3.0, material()).unwrap();
+            let n = normal.unit_vector();
+            assert!((plane.normal - n).norm() < 1e-12);
+            // a point on the plane, off to the side of the anchor
+            let side = n.cross(Point3 {x: 0.3, y: -0.5, z: 0.7}).unit_vector();
+            let target = point + side * 4.0;
+            for (start, facing) in [(point + n * 2.0, n), (point - n * 2.0, -n)] {
+                let hit = plane.ray_hit(&Ray {A: target - start, B: start, time: 0.0}, (MINIMUM, INFINITY)).unwrap();
+                assert!((hit.t - 1.0).abs() < 1e-12);
+                assert!((hit.point - target).norm() < 1e-12);
+                assert!((hit.normal - facing).norm() < 1e-12);
+                assert_eq!(matches!(hit.face, Face::FrontFace), facing.dot(n) > 0.0);
+            }
+            assert!(plane.ray_hit(&Ray {A: side, B: point + n, time: 0.0}, (MINIMUM, INFINITY)).is_none());
+            assert!(plane.ray_hit(&Ray {A: n, B: point + n, time: 0.0}, (MINIMUM, INFINITY)).is_none());
+        }
+        // three points face the same way as a triangle through them
+        let (a, b, c) = (Point3 {x: 0.0, y: 5.0, z: 0.0}, Point3 {x: 1.0, y: 5.0, z: 0.0}, Point3 {x: 0.0, y: 5.0, z: 1.0});
+        assert!((Plane::from_points(a, b, c, material()).unwrap().normal - Point3 {x: 0.0, y: -1.0, z: 0.0}).norm() < 1e-12);
+        assert!(matches!(Plane::construct(point, ORIGIN, material()), Err(RaytraceError::ZeroNormal)));
+        assert!(matches!(Plane::from_points(a, b, b * 2.0 - a, material()), Err(RaytraceError::ZeroNormal)));
+    }
+
+    #[test]
+    fn spheres_need_a_finite_positive_radius() {
+        for radius in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(matches!(Sphere::construct(ORIGIN, radius, material()), Err(RaytraceError::InvalidRadius(_))));
+        }
+        assert!(Sphere::construct(ORIGIN, 1e-9, material()).is_ok());
+    }
+
+    #[test]
+    fn mesh_pdf_counts_every_triangle_along_the_ray() {
+        // two triangles of area 8, at heights 1 and 2, straight over the origin
+        let vertices = vec![
+            Point3 {x: -1.0, y: -1.0, z: 1.0}, Point3 {x: 3.0, y: -1.0, z: 1.0}, Point3 {x: -1.0, y: 3.0, z: 1.0},
+            Point3 {x: -1.0, y: -1.0, z: 2.0}, Point3 {x: 3.0, y: -1.0, z: 2.0}, Point3 {x: -1.0, y: 3.0, z: 2.0}
+        ];
+        let mesh = Mesh::construct(vertices, vec![[0, 1, 2], [3, 4, 5]], None, None, material()).unwrap();
+        let pdf = mesh.pdf_towards(&Ray::construct(UNIT_Z, ORIGIN));
+        // each point has density 1/16 by area, and 1/16 times the distance squared by solid angle
+        assert!((pdf - (1.0 + 4.0) / 16.0).abs() < 1e-12, "pdf {}", pdf);
+    }
+
+    #[test]
+    fn moving_lights_are_sampled_where_they_are() {
+        let lamp = || Box::new(crate::material::LightSource::construct(1.0, 1.0, 1.0, 1.0).unwrap()) as Box<dyn Material>;
+        let start = Point3 {x: 0.0, y: 5.0, z: 0.0};
+        let moving = Moving::construct(Box::new(Sphere::construct(start, 0.5, lamp()).unwrap()), UNIT_X * 4.0, ORIGIN, UNIT_Z, 0.3).unwrap();
+        let time = 0.5;
+        let center = start.rotate(UNIT_Z, 0.15) + UNIT_X * 2.0;  // spun about the origin, then moved
+        let still = Sphere::construct(center, 0.5, lamp()).unwrap();
+        assert!(moving.is_emitter());
+
+        let mut rng = Pcg32::construct(11, 0);
+        for _ in 0..100 {
+            let direction = moving.sample_towards(ORIGIN, time, (rng.random(), rng.random())).unwrap();
+            let ray = Ray::construct(direction, ORIGIN).with_time(time);
+            assert!(moving.ray_hit(&ray, (MINIMUM, INFINITY)).is_some());
+            assert!((moving.pdf_towards(&ray) - still.pdf_towards(&ray)).abs() < 1e-9);
+        }
+    }
+
+    // light reflected by a diffuse surface of albedo 0.5 at origin, facing up, from the disk
+    // giving off radiance 1, estimated by sampling the disk as a light
+    fn direct_light(disk: &Disk, origin: Point3, rng: &mut Pcg32) -> f64 {
+        let samples = 100000;
+        let mut total = 0.0;
+        for _ in 0..samples {
+            let direction = disk.sample_towards(origin, 0.0, (rng.random(), rng.random())).unwrap();
+            let ray = Ray {A: direction, B: origin, time: 0.0};
+            if disk.ray_hit(&ray, (MINIMUM, INFINITY)).is_some() {
+                total += direction.z / disk.pdf_towards(&ray);
+            }
+        }
+        total / samples as f64 * 0.5 / std::f64::consts::PI
+    }
+
+    #[test]
+    fn area_and_cone_sampling_light_the_same() {
+        let mut rng = Pcg32::construct(5, 0);
+        // a disk of radius 1 at height 2 covers directions up to sin^2 = 1/5 from straight up
+        let mut disk = Disk::construct(Point3 {x: 0.0, y: 0.0, z: 2.0}, -UNIT_Z, 1.0, material()).unwrap();
+        for (origin, expected) in [(ORIGIN, Some(0.1)), (Point3 {x: 1.5, y: 0.5, z: 0.0}, None)] {
+            disk.sampling = LightSampling::Area;
+            let area = direct_light(&disk, origin, &mut rng);
+            disk.sampling = LightSampling::BoundingCone;
+            let cone = direct_light(&disk, origin, &mut rng);
+            assert!((area - cone).abs() < 0.01 * area, "{} vs {}", area, cone);
+            if let Some(expected) = expected {
+                assert!((area - expected).abs() < 0.01 * expected, "{} vs {}", area, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn cone_samples_have_the_cone_pdf() {
+        let mut rng = Pcg32::construct(6, 0);
+        let mut disk = Disk::construct(Point3 {x: 0.0, y: 0.0, z: 2.0}, -UNIT_Z, 1.0, material()).unwrap();
+        disk.sampling = LightSampling::BoundingCone;
+        // the bounding sphere of radius 1 at distance 2 spans a cone of half angle 30 degrees
+        let cos_max = 0.75f64.sqrt();
+        let samples = 100000;
+        let mut hits = 0;
+        for _ in 0..samples {
+            let direction = disk.sample_towards(ORIGIN, 0.0, (rng.random(), rng.random())).unwrap();
+            assert!(direction.z >= cos_max - 1e-12);
+            let ray = Ray {A: direction, B: ORIGIN, time: 0.0};
+            assert!((disk.pdf_towards(&ray) - cone_pdf(cos_max)).abs() < 1e-9);
+            hits += disk.ray_hit(&ray, (MINIMUM, INFINITY)).is_some() as usize;
+        }
+        // samples hit the disk in proportion to the solid angle it covers within the cone
+        let expected = (1.0 - 2.0 / 5.0f64.sqrt()) / (1.0 - cos_max);
+        assert!((hits as f64 / samples as f64 - expected).abs() < 0.01, "{} vs {}", hits as f64 / samples as f64, expected);
+        let outside = Ray {A: Point3 {x: 0.6, y: 0.0, z: 0.8}, B: ORIGIN, time: 0.0};
+        assert_eq!(disk.pdf_towards(&outside), 0.0);
+    }
+
+    #[test]
+    fn tied_hits_go_to_the_first_object() {
+        let spheres: Vec<Box<dyn Object>> = (0..3)
+            .map(|_| Box::new(Sphere::construct(ORIGIN, 1.0, material()).unwrap()) as Box<dyn Object>)
+            .collect();
+        let planes: Vec<Box<dyn Object>> = (0..3)
+            .map(|_| Box::new(Plane::construct(Point3 {x: 0.0, y: 0.0, z: -1.0}, UNIT_Z, material()).unwrap()) as Box<dyn Object>)
+            .collect();
+        for objects in [spheres, planes] {
+            let world = World::construct(objects);
+            let first = world.objects()[0].ray_hit(&Ray::construct(-UNIT_Z, UNIT_Z * 5.0), (MINIMUM, INFINITY)).unwrap().material;
+            for ray in [Ray::construct(-UNIT_Z, UNIT_Z * 5.0), Ray::construct(Point3 {x: 0.05, y: 0.02, z: -1.0}, Point3 {x: 0.3, y: 0.0, z: 4.0})] {
+                assert_same_hit(&ray, &world);
+                assert!(std::ptr::addr_eq(world.hit(&ray, (MINIMUM, INFINITY)).unwrap().material, first));
+            }
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use rand::RngCore;
+use rand::rand_core::impls;
+
+/// PCG-XSH-RR random numbers (O'Neill, 2014): small and fast, with independent streams. Every
+/// sample of every pixel gets its own stream, derived from the render seed, so an image does
+/// not depend on which thread renders which pixel, or in what order.
+#[derive(Clone, Debug)]
+pub struct Pcg32 {
+    state: u64,
+    increment: u64  // odd, selects the stream
+}
+
+const MULTIPLIER: u64 = 6364136223846793005;
+
+// the splitmix64 finalizer, to spread nearby inputs over all bits
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+impl Pcg32 {
+    pub fn construct(seed: u64, stream: u64) -> Pcg32 {
+        let mut rng = Pcg32 {state: 0, increment: (stream << 1) | 1};
+        rng.step();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.step();
+        rng
+    }
+    /// The stream for one sample of the pixel in column `x` and row `y`.
+    pub fn for_sample(seed: u64, x: u32, y: u32, sample: u32) -> Pcg32 {
+        let pixel = mix(seed ^ mix(((y as u64) << 32) | x as u64));
+        Pcg32::construct(mix(pixel ^ sample as u64), pixel)
+    }
+    fn step(&mut self) {
+        self.state = self.state.wrapping_mul(MULTIPLIER).wrapping_add(self.increment);
+    }
+}
+
+impl RngCore for Pcg32 {
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.step();
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+    fn next_u64(&mut self) -> u64 {
+        impls::next_u64_via_u32(self)
+    }
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        impls::fill_bytes_via_next(self, dst)
+    }
+}
@@ -0,0 +1,848 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::utils::{Point3, Color3, Material, ORIGIN, UNIT_Y, UNIT_Z, WHITE};
+use crate::object::{World, Object, Sphere, Plane, Triangle, Quad, Disk, LightSampling, Moving};
+use crate::material::{Metallic, Diffuse, Dielectric, LightSource};
+use crate::light::{PointLight, SpotLight, DirectionalLight};
+use crate::background::{Solid, Black, Gradient, Checker};
+use crate::environment::EnvironmentMap;
+use crate::sky::Sky;
+use crate::camera::{Camera, View, Projection, Aperture, Focus, RenderSettings};
+use crate::obj;
+use crate::error::RaytraceError;
+
+// Scene description files. A scene is a list of blocks:
+//
+//     # comments run to the end of the line
+//     settings { width = 640  aspect = 16:9  samples = 8  depth = 10 }
+//     camera { look_from = (0, -2, 1)  look_at = (0, 1, 0)  up = (0, 0, 1)  fov = 60  f_stop = 2.8  focus = auto }
+//     background { style = gradient  nadir = (1, 1, 1)  zenith = (0.4, 0.6, 1) }
+//     material gold = metal { albedo = (0.8, 0.6, 0.2)  fuzz = 0.1 }
+//     material lamp = light { color = (1, 0.9, 0.8)  intensity = 4 }
+//     sphere { center = (1, 1, 0)  radius = 0.5  material = gold }
+//     spot_light { position = (0, 0, 3)  look_at = (1, 1, 0)  angle = 30  falloff = 5  intensity = 20 }
+//     mesh { file = "teapot.obj"  spin = 30 }
+//
+// The camera looks down +Y with +Z up and a 90 degree vertical field of view unless told
+// otherwise; look_at defaults to one unit ahead of look_from. It is a pinhole camera unless
+// given a lens radius (aperture) or f_stop, and then focuses on look_at, at a focus distance,
+// or on what is in the center of the image (focus = auto). The projection is perspective,
+// orthographic (showing a view height, by default what the fov shows at look_at), fisheye
+// (equidistant, fov up to 360) or equirectangular (all directions, ignoring fov). Samples are
+// taken at times between shutter_open and shutter_close (0 and 1 unless given).
+// Rays that hit nothing see the background, a blue sky if none is given. Its style is solid
+// { color } (the default), black, gradient { nadir zenith up } from straight down to straight
+// up, or checker { even odd size } in squares of size degrees. An environment { file rotation
+// intensity } block replaces the background with an equirectangular .hdr, .pfm or .exr image
+// (+Y in its middle, +Z up), turned by rotation degrees about +Z, that also lights the scene.
+// A sky { elevation azimuth turbidity ground_albedo intensity } block replaces it with
+// daylight for a sun at elevation degrees above the horizon and azimuth degrees from +Y
+// towards +X, in air of turbidity 1.7 (clear) to 10 (hazy, 3 by default), over ground of
+// ground_albedo, with the sun's disk in it lighting the scene unless sun = false.
+// Materials are diffuse { albedo }, metal { albedo fuzz }, dielectric { ior } and
+// light { color intensity two_sided }, which gives off color times intensity (both 1 by
+// default) from both sides, or only along the normal with two_sided = false; objects are
+// sphere { center radius }, plane { point normal }, triangle { a b c }, quad { corner u v }
+// (a parallelogram with edges u and v), disk { center normal radius } and mesh { file }
+// (materials from its .mtl unless one is given). Quads and disks used as lights are sampled
+// uniformly by area, or with sampling = bounding_cone uniformly over the directions towards
+// their bounding sphere, which wastes the samples that miss them. Lights are objects with a
+// light material, or point_light { position }, spot_light { position direction (or look_at)
+// angle falloff } with angles in degrees from the axis, and sun { direction angular_diameter },
+// all with color and intensity; these cannot be seen, only lit by. Any object can move: by
+// move = (x, y, z) and spin = degrees about axis (0, 0, 1 by default) through pivot (its
+// center by default), from time 0 to 1.
+// Values are numbers, (x, y, z) vectors, "strings" or names.
+
+/// Error loading a scene file, with the line and column for parse errors.
+#[derive(Debug)]
+pub enum SceneError {
+    Io {file: PathBuf, error: std::io::Error},
+    Parse {file: PathBuf, line: usize, column: usize, message: String},
+    Invalid {file: PathBuf, error: RaytraceError}  // a mesh file that parses but is not valid
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io {file, error} => write!(f, "{}: {}", file.display(), error),
+            SceneError::Parse {file, line, column, message} => write!(f, "{}:{}:{}: {}", file.display(), line, column, message),
+            SceneError::Invalid {file, error} => write!(f, "{}: {}", file.display(), error)
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Position {
+    line: usize,
+    column: usize
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Name(String),
+    Number(f64),
+    Text(String),
+    Symbol(char)
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Name(name) => write!(f, "'{}'", name),
+            Token::Number(n) => write!(f, "number {}", n),
+            Token::Text(text) => write!(f, "\"{}\"", text),
+            Token::Symbol(c) => write!(f, "'{}'", c)
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Number(f64),
+    Vector(Point3),
+    Text(String),
+    Name(String)
+}
+
+impl Value {
+    fn describe(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "a number",
+            Value::Vector(_) => "a vector",
+            Value::Text(_) => "a string",
+            Value::Name(_) => "a name"
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Property {
+    key: String,
+    value: Value,
+    position: Position
+}
+
+#[derive(Clone, Debug)]
+struct Block {
+    kind: String,
+    name: Option<String>,       // material <name> = <kind> { ... }
+    properties: Vec<Property>,
+    position: Position
+}
+
+struct Parser<'a> {
+    file: &'a Path,
+    tokens: Vec<(Token, Position)>,
+    next: usize,
+    end: Position
+}
+
+impl Parser<'_> {
+    fn error(&self, position: Position, message: String) -> SceneError {
+        SceneError::Parse {file: self.file.to_path_buf(), line: position.line, column: position.column, message}
+    }
+    fn tokenize<'a>(file: &'a Path, source: &str) -> Result<Parser<'a>, SceneError> {
+        let mut parser = Parser {file, tokens: Vec::new(), next: 0, end: Position {line: 1, column: 1}};
+        let chars: Vec<char> = source.chars().collect();
+        let (mut i, mut line, mut column) = (0, 1, 1);
+        while i < chars.len() {
+            let position = Position {line, column};
+            let c = chars[i];
+            let start = i;
+            if c == '\n' {
+                (i, line, column) = (i + 1, line + 1, 1);
+                continue;
+            } else if c.is_whitespace() {
+                i += 1;
+            } else if c == '#' {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            } else if c.is_ascii_alphabetic() || c == '_' {
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                parser.tokens.push((Token::Name(chars[start..i].iter().collect()), position));
+            } else if c.is_ascii_digit() || ((c == '-' || c == '+' || c == '.') && chars.get(i + 1).is_some_and(|n| n.is_ascii_digit() || *n == '.')) {
+                i += 1;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.'
+                    || ((chars[i] == 'e' || chars[i] == 'E') && chars.get(i + 1).is_some_and(|n| n.is_ascii_digit() || *n == '-' || *n == '+'))
+                    || ((chars[i] == '-' || chars[i] == '+') && (chars[i - 1] == 'e' || chars[i - 1] == 'E'))) {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                let number = text.parse().map_err(|_| parser.error(position, format!("invalid number '{}'", text)))?;
+                parser.tokens.push((Token::Number(number), position));
+            } else if c == '"' {
+                i += 1;
+                while i < chars.len() && chars[i] != '"' && chars[i] != '\n' {
+                    i += 1;
+                }
+                if chars.get(i) != Some(&'"') {
+                    return Err(parser.error(position, "unterminated string".to_string()));
+                }
+                i += 1;
+                parser.tokens.push((Token::Text(chars[start + 1..i - 1].iter().collect()), position));
+            } else if "{}()=,:".contains(c) {
+                i += 1;
+                parser.tokens.push((Token::Symbol(c), position));
+            } else {
+                return Err(parser.error(position, format!("unexpected character '{}'", c)));
+            }
+            column += i - start;
+        }
+        parser.end = Position {line, column};
+        Ok(parser)
+    }
+    fn peek(&self) -> Option<&(Token, Position)> {
+        self.tokens.get(self.next)
+    }
+    fn advance(&mut self, expected: &str) -> Result<(Token, Position), SceneError> {
+        match self.tokens.get(self.next) {
+            Some(token) => {
+                self.next += 1;
+                Ok(token.clone())
+            },
+            None => Err(self.error(self.end, format!("expected {}, found the end of the file", expected)))
+        }
+    }
+    fn symbol(&mut self, symbol: char) -> Result<Position, SceneError> {
+        match self.advance(&format!("'{}'", symbol))? {
+            (Token::Symbol(c), position) if c == symbol => Ok(position),
+            (token, position) => Err(self.error(position, format!("expected '{}', found {}", symbol, token)))
+        }
+    }
+    fn name(&mut self, what: &str) -> Result<(String, Position), SceneError> {
+        match self.advance(what)? {
+            (Token::Name(name), position) => Ok((name, position)),
+            (token, position) => Err(self.error(position, format!("expected {}, found {}", what, token)))
+        }
+    }
+    fn number(&mut self) -> Result<f64, SceneError> {
+        match self.advance("a number")? {
+            (Token::Number(n), _) => Ok(n),
+            (token, position) => Err(self.error(position, format!("expected a number, found {}", token)))
+        }
+    }
+    fn value(&mut self) -> Result<Value, SceneError> {
+        match self.advance("a value")? {
+            (Token::Number(n), _) => {
+                if let Some((Token::Symbol(':'), _)) = self.peek() {  // a ratio, like 16:9
+                    self.next += 1;
+                    return Ok(Value::Number(n / self.number()?));
+                }
+                Ok(Value::Number(n))
+            },
+            (Token::Text(text), _) => Ok(Value::Text(text)),
+            (Token::Name(name), _) => Ok(Value::Name(name)),
+            (Token::Symbol('('), _) => {
+                let x = self.number()?;
+                self.symbol(',')?;
+                let y = self.number()?;
+                self.symbol(',')?;
+                let z = self.number()?;
+                self.symbol(')')?;
+                Ok(Value::Vector(Point3 {x, y, z}))
+            },
+            (token, position) => Err(self.error(position, format!("expected a value, found {}", token)))
+        }
+    }
+    fn block(&mut self) -> Result<Block, SceneError> {
+        let (mut kind, position) = self.name("a block")?;
+        let mut name = None;
+        if kind == "material" {
+            name = Some(self.name("a material name")?.0);
+            self.symbol('=')?;
+            kind = self.name("a material kind")?.0;
+        }
+        self.symbol('{')?;
+        let mut properties: Vec<Property> = Vec::new();
+        loop {
+            match self.peek() {
+                Some((Token::Symbol('}'), _)) => {
+                    self.next += 1;
+                    break;
+                },
+                Some((Token::Symbol(','), _)) => self.next += 1,
+                _ => {
+                    let (key, position) = self.name("a property name or '}'")?;
+                    if properties.iter().any(|p| p.key == key) {
+                        return Err(self.error(position, format!("'{}' is given twice", key)));
+                    }
+                    self.symbol('=')?;
+                    properties.push(Property {key, value: self.value()?, position});
+                }
+            }
+        }
+        Ok(Block {kind, name, properties, position})
+    }
+}
+
+// the properties of one block, taken one at a time so leftovers can be reported as unknown
+struct Properties<'a> {
+    parser: &'a Parser<'a>,
+    block: Block
+}
+
+impl Properties<'_> {
+    fn take(&mut self, key: &str) -> Option<Property> {
+        let i = self.block.properties.iter().position(|p| p.key == key)?;
+        Some(self.block.properties.remove(i))
+    }
+    fn wrong_type(&self, property: &Property, expected: &str) -> SceneError {
+        self.parser.error(property.position, format!("'{}' should be {}, found {}", property.key, expected, property.value.describe()))
+    }
+    fn missing(&self, key: &str) -> SceneError {
+        self.parser.error(self.block.position, format!("{} needs '{}'", self.block.kind, key))
+    }
+    fn number(&mut self, key: &str) -> Result<Option<(f64, Position)>, SceneError> {
+        match self.take(key) {
+            Some(Property {value: Value::Number(n), position, ..}) => Ok(Some((n, position))),
+            Some(property) => Err(self.wrong_type(&property, "a number")),
+            None => Ok(None)
+        }
+    }
+    fn vector(&mut self, key: &str) -> Result<Option<(Point3, Position)>, SceneError> {
+        match self.take(key) {
+            Some(Property {value: Value::Vector(v), position, ..}) => Ok(Some((v, position))),
+            Some(property) => Err(self.wrong_type(&property, "a vector (x, y, z)")),
+            None => Ok(None)
+        }
+    }
+    fn text(&mut self, key: &str) -> Result<Option<(String, Position)>, SceneError> {
+        match self.take(key) {
+            Some(Property {value: Value::Text(text), position, ..}) => Ok(Some((text, position))),
+            Some(property) => Err(self.wrong_type(&property, "a \"string\"")),
+            None => Ok(None)
+        }
+    }
+    fn name(&mut self, key: &str) -> Result<Option<(String, Position)>, SceneError> {
+        match self.take(key) {
+            Some(Property {value: Value::Name(name), position, ..}) => Ok(Some((name, position))),
+            Some(property) => Err(self.wrong_type(&property, "a name")),
+            None => Ok(None)
+        }
+    }
+    fn required_number(&mut self, key: &str) -> Result<(f64, Position), SceneError> {
+        self.number(key)?.ok_or_else(|| self.missing(key))
+    }
+    fn required_vector(&mut self, key: &str) -> Result<(Point3, Position), SceneError> {
+        self.vector(key)?.ok_or_else(|| self.missing(key))
+    }
+    fn integer<T: TryFrom<i64>>(&mut self, key: &str, minimum: i64) -> Result<Option<T>, SceneError> {
+        let Some((n, position)) = self.number(key)? else { return Ok(None) };
+        if n.fract() != 0.0 || n < minimum as f64 {
+            return Err(self.parser.error(position, format!("'{}' should be a whole number of at least {}", key, minimum)));
+        }
+        // i64::MAX as f64 rounds up to 2^63, which is already out of range
+        let too_large = || self.parser.error(position, format!("'{}' is too large", key));
+        if n >= i64::MAX as f64 {
+            return Err(too_large());
+        }
+        T::try_from(n as i64).map(Some).map_err(|_| too_large())
+    }
+    fn color(&mut self, key: &str) -> Result<Point3, SceneError> {
+        let (c, position) = self.required_vector(key)?;
+        if [c.x, c.y, c.z].iter().any(|v| !(0.0..=1.0).contains(v)) {
+            return Err(self.parser.error(position, format!("'{}' components must be between 0 and 1", key)));
+        }
+        Ok(c)
+    }
+    fn material<'m>(&mut self, materials: &'m [(String, MaterialSpec)]) -> Result<&'m MaterialSpec, SceneError> {
+        let (name, position) = self.name("material")?.ok_or_else(|| self.missing("material"))?;
+        match materials.iter().find(|(n, _)| *n == name) {
+            Some((_, spec)) => Ok(spec),
+            None => Err(self.parser.error(position, format!("undefined material '{}'", name)))
+        }
+    }
+    fn boolean(&mut self, key: &str) -> Result<Option<bool>, SceneError> {
+        match self.name(key)? {
+            Some((value, _)) if value == "true" => Ok(Some(true)),
+            Some((value, _)) if value == "false" => Ok(Some(false)),
+            Some((_, position)) => Err(self.parser.error(position, format!("'{}' should be true or false", key))),
+            None => Ok(None)
+        }
+    }
+    // a color that is light rather than a reflectance, so may be above 1
+    fn radiance(&mut self, key: &str) -> Result<Option<Color3>, SceneError> {
+        match self.vector(key)? {
+            Some((c, position)) if [c.x, c.y, c.z].iter().any(|v| *v < 0.0) =>
+                Err(self.parser.error(position, format!("'{}' components cannot be negative", key))),
+            Some((c, _)) => Ok(Some(Color3::from_point3(c))),
+            None => Ok(None)
+        }
+    }
+    // color and intensity of a light, both 1 by default
+    fn emission(&mut self) -> Result<(Color3, f64), SceneError> {
+        let color = self.radiance("color")?.unwrap_or(WHITE);
+        let intensity = match self.number("intensity")? {
+            Some((intensity, position)) if intensity < 0.0 =>
+                return Err(self.parser.error(position, "'intensity' cannot be negative".to_string())),
+            Some((intensity, _)) => intensity,
+            None => 1.0
+        };
+        Ok((color, intensity))
+    }
+    fn sampling(&mut self) -> Result<LightSampling, SceneError> {
+        match self.name("sampling")? {
+            Some((name, position)) => LightSampling::from_name(&name)
+                .ok_or_else(|| self.parser.error(position, format!("unknown light sampling '{}', expected area or bounding_cone", name))),
+            None => Ok(LightSampling::Area)
+        }
+    }
+    // None if the object neither moves nor spins
+    fn motion(&mut self) -> Result<Option<MotionSpec>, SceneError> {
+        let translation = self.vector("move")?.map(|(v, _)| v);
+        let spin = self.number("spin")?.map(|(n, _)| n);
+        let axis = self.vector("axis")?.map_or(UNIT_Z, |(v, _)| v);
+        let pivot = self.vector("pivot")?.map(|(v, _)| v);
+        if translation.is_none() && spin.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(MotionSpec {translation: translation.unwrap_or(ORIGIN), spin: spin.unwrap_or(0.0), axis, pivot}))
+    }
+    fn finish(self) -> Result<(), SceneError> {
+        match self.block.properties.first() {
+            Some(property) => Err(self.parser.error(property.position, format!("unknown property '{}' for {}", property.key, self.block.kind))),
+            None => Ok(())
+        }
+    }
+}
+
+// material definitions are kept as data, so each object using one gets its own Box<dyn Material>
+#[derive(Clone, Debug)]
+enum MaterialSpec {
+    Diffuse(Point3),
+    Metal(Point3, f64),
+    Dielectric(f64),
+    Light(Point3, f64, bool)  // color, intensity, two-sided
+}
+
+impl MaterialSpec {
+    fn build(&self) -> Result<Box<dyn Material>, RaytraceError> {
+        Ok(match *self {
+            MaterialSpec::Diffuse(c) => Box::new(Diffuse::construct(c.x, c.y, c.z)?),
+            MaterialSpec::Metal(c, fuzz) => Box::new(Metallic::construct(c.x, c.y, c.z, fuzz)?),
+            MaterialSpec::Dielectric(refractive_index) => Box::new(Dielectric::construct(refractive_index)?),
+            MaterialSpec::Light(c, intensity, two_sided) => {
+                let light = LightSource::construct(c.x, c.y, c.z, intensity)?;
+                Box::new(if two_sided { light } else { light.one_sided() })
+            }
+        })
+    }
+}
+
+// how an object moves from time 0 to 1
+#[derive(Clone, Debug)]
+struct MotionSpec {
+    translation: Point3,   // move
+    spin: f64,             // degrees about axis through pivot
+    axis: Point3,
+    pivot: Option<Point3>  // the center of the object unless given
+}
+
+/// A world with its camera and render settings, as loaded from a scene file.
+pub struct Scene {
+    pub world: World,
+    pub view: View,
+    pub settings: RenderSettings
+}
+
+impl Scene {
+    pub fn construct(world: World) -> Scene {
+        Scene {world, view: View::DEFAULT, settings: RenderSettings::default()}
+    }
+    /// Loads a scene: a .obj file becomes a scene of its meshes, anything else is read as a
+    /// scene description.
+    pub fn load(path: &Path) -> Result<Scene, SceneError> {
+        if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("obj")) {
+            let mut world = World::construct(Vec::new());
+            world.load_obj(path).map_err(|error| match error {
+                obj::ObjError::Io {file, error} => SceneError::Io {file, error},
+                obj::ObjError::Parse {file, line, message} => SceneError::Parse {file, line, column: 1, message},
+                obj::ObjError::Invalid {file, error} => SceneError::Invalid {file, error}
+            })?;
+            return Ok(Scene::construct(world));
+        }
+        let source = fs::read_to_string(path).map_err(|error| SceneError::Io {file: path.to_path_buf(), error})?;
+        Self::parse(&source, path)
+    }
+    /// Parses a scene description; `file` is used for error messages and to find meshes
+    /// relative to the scene.
+    pub fn parse(source: &str, file: &Path) -> Result<Scene, SceneError> {
+        let mut parser = Parser::tokenize(file, source)?;
+        let mut blocks = Vec::new();
+        while parser.peek().is_some() {
+            blocks.push(parser.block()?);
+        }
+        let directory = file.parent().unwrap_or(Path::new(""));
+
+        let mut scene = Scene::construct(World::construct(Vec::new()));
+        let mut materials: Vec<(String, MaterialSpec)> = Vec::new();
+        let (mut seen_camera, mut seen_settings, mut seen_background) = (false, false, false);
+        for block in blocks {
+            let position = block.position;
+            let name = block.name.clone();
+            let mut properties = Properties {parser: &parser, block};
+            let kind = properties.block.kind.clone();
+            let invalid = |error: RaytraceError| parser.error(position, error.to_string());
+            let mut added: Vec<Box<dyn Object>> = Vec::new();
+            let motion = match (kind.as_str(), &name) {
+                ("sphere" | "plane" | "triangle" | "quad" | "disk" | "mesh", None) => properties.motion()?,
+                _ => None
+            };
+            match (kind.as_str(), name) {
+                ("settings", None) => {
+                    if seen_settings {
+                        return Err(parser.error(position, "settings are given twice".to_string()));
+                    }
+                    seen_settings = true;
+                    let settings = &mut scene.settings;
+                    settings.width = properties.integer("width", 1)?;
+                    settings.height = properties.integer("height", 1)?;
+                    if let Some((aspect, position)) = properties.number("aspect")? {
+                        if !aspect.is_finite() || aspect <= 0.0 {
+                            return Err(parser.error(position, "'aspect' must be positive".to_string()));
+                        }
+                        settings.aspect = Some(aspect);
+                    }
+                    settings.pixel_samples = properties.integer("samples", 1)?;
+                    settings.scatter_depth = properties.integer("depth", 1)?;
+                    settings.seed = properties.integer("seed", 0)?;
+                    settings.threads = properties.integer("threads", 0)?;
+                },
+                ("camera", None) => {
+                    if seen_camera {
+                        return Err(parser.error(position, "the camera is given twice".to_string()));
+                    }
+                    seen_camera = true;
+                    let look_from = properties.vector("look_from")?.map_or(View::DEFAULT.look_from, |(v, _)| v);
+                    let look_at = properties.vector("look_at")?.map_or(look_from + UNIT_Y, |(v, _)| v);
+                    let vup = properties.vector("up")?.map_or(View::DEFAULT.vup, |(v, _)| v);
+                    let vfov = properties.number("fov")?.map_or(View::DEFAULT.vfov, |(v, _)| v);
+                    scene.view = View::construct(look_from, look_at, vup, vfov);
+                    let open = properties.number("shutter_open")?.map_or(View::DEFAULT.shutter.0, |(n, _)| n);
+                    let close = properties.number("shutter_close")?.map_or(View::DEFAULT.shutter.1, |(n, _)| n);
+                    if close < open {
+                        return Err(parser.error(position, "the shutter closes before it opens".to_string()));
+                    }
+                    scene.view.shutter = (open, close);
+                    if let Some((name, position)) = properties.name("projection")? {
+                        scene.view.projection = Projection::from_name(&name)
+                            .ok_or_else(|| parser.error(position, format!("unknown projection '{}'", name)))?;
+                    }
+                    if let Some((height, position)) = properties.number("height")? {
+                        if scene.view.projection != Projection::Orthographic(None) {
+                            return Err(parser.error(position, "'height' is only for the orthographic projection".to_string()));
+                        }
+                        scene.view.projection = Projection::Orthographic(Some(height));
+                    }
+                    scene.view.aperture = match (properties.number("aperture")?, properties.number("f_stop")?) {
+                        (Some(_), Some((_, position))) => return Err(parser.error(position, "give either 'aperture' or 'f_stop', not both".to_string())),
+                        (Some((radius, _)), None) => Aperture::Radius(radius),
+                        (None, Some((f_stop, _))) => Aperture::FStop(f_stop),
+                        (None, None) => View::DEFAULT.aperture
+                    };
+                    scene.view.focus = match properties.take("focus") {
+                        Some(Property {value: Value::Number(distance), ..}) => Focus::Distance(distance),
+                        Some(Property {value: Value::Name(name), ..}) if name == "auto" => Focus::Auto,
+                        Some(property) => return Err(properties.wrong_type(&property, "a distance or auto")),
+                        None => View::DEFAULT.focus
+                    };
+                    scene.view.basis().map_err(|error| parser.error(position, error.to_string()))?;
+                    scene.view.lens_radius().map_err(|error| parser.error(position, error.to_string()))?;
+                    if let Projection::Orthographic(Some(height)) = scene.view.projection && height <= 0.0 {
+                        return Err(parser.error(position, "'height' must be positive".to_string()));
+                    }
+                    if let Focus::Distance(distance) = scene.view.focus && distance <= 0.0 {
+                        return Err(parser.error(position, "'focus' must be positive".to_string()));
+                    }
+                },
+                ("sky", None) => {
+                    if seen_background {
+                        return Err(parser.error(position, "the background is given twice (a sky is one too)".to_string()));
+                    }
+                    seen_background = true;
+                    let elevation = properties.required_number("elevation")?.0;
+                    let azimuth = properties.number("azimuth")?.map_or(0.0, |(n, _)| n);
+                    let turbidity = properties.number("turbidity")?.map_or(3.0, |(n, _)| n);
+                    let ground = properties.vector("ground_albedo")?.map_or(Color3 {r: 0.3, g: 0.3, b: 0.3}, |(c, _)| Color3::from_point3(c));
+                    let mut sky = Sky::construct(elevation, azimuth, turbidity, ground).map_err(invalid)?;
+                    if let Some((intensity, position)) = properties.number("intensity")? {
+                        if intensity < 0.0 {
+                            return Err(parser.error(position, "'intensity' cannot be negative".to_string()));
+                        }
+                        sky.intensity = intensity;
+                    }
+                    sky.sun = properties.boolean("sun")?.unwrap_or(true);
+                    scene.world.background = Box::new(sky);
+                },
+                ("background", None) | ("environment", None) => {
+                    if seen_background {
+                        return Err(parser.error(position, "the background is given twice (an environment is one too)".to_string()));
+                    }
+                    seen_background = true;
+                    scene.world.background = if kind == "environment" {
+                        let (file, position) = properties.text("file")?.ok_or_else(|| properties.missing("file"))?;
+                        let mut environment = EnvironmentMap::load(&directory.join(&file))
+                            .map_err(|error| parser.error(position, format!("cannot load environment: {}", error)))?;
+                        environment.rotation = properties.number("rotation")?.map_or(0.0, |(n, _)| n);
+                        environment.intensity = match properties.number("intensity")? {
+                            Some((intensity, position)) if intensity < 0.0 =>
+                                return Err(parser.error(position, "'intensity' cannot be negative".to_string())),
+                            Some((intensity, _)) => intensity,
+                            None => 1.0
+                        };
+                        Box::new(environment)
+                    } else {
+                        let (style, style_position) = properties.name("style")?.unwrap_or(("solid".to_string(), position));
+                        match style.as_str() {
+                            "solid" => Box::new(Solid {color: properties.radiance("color")?.ok_or_else(|| properties.missing("color"))?}),
+                            "black" => Box::new(Black),
+                            "gradient" => {
+                                let nadir = properties.radiance("nadir")?.unwrap_or(Gradient::SKY.nadir);
+                                let zenith = properties.radiance("zenith")?.unwrap_or(Gradient::SKY.zenith);
+                                let up = properties.vector("up")?.map_or(Gradient::SKY.up, |(v, _)| v);
+                                Box::new(Gradient::construct(nadir, zenith, up).map_err(invalid)?)
+                            },
+                            "checker" => {
+                                let even = properties.radiance("even")?.unwrap_or(WHITE);
+                                let odd = properties.radiance("odd")?.unwrap_or(Color3 {r: 0.2, g: 0.2, b: 0.2});
+                                let size = properties.number("size")?.map_or(15.0, |(n, _)| n);
+                                Box::new(Checker::construct(even, odd, size).map_err(invalid)?)
+                            },
+                            other => return Err(parser.error(style_position,
+                                format!("unknown background style '{}', expected solid, black, gradient or checker", other)))
+                        }
+                    };
+                },
+                (kind, Some(name)) => {
+                    if materials.iter().any(|(n, _)| *n == name) {
+                        return Err(parser.error(position, format!("material '{}' is defined twice", name)));
+                    }
+                    let spec = match kind {
+                        "diffuse" => MaterialSpec::Diffuse(properties.color("albedo")?),
+                        "metal" => {
+                            let albedo = properties.color("albedo")?;
+                            let fuzz = match properties.number("fuzz")? {
+                                Some((fuzz, _)) if (0.0..=1.0).contains(&fuzz) => fuzz,
+                                Some((_, position)) => return Err(parser.error(position, "'fuzz' must be between 0 and 1".to_string())),
+                                None => 0.0
+                            };
+                            MaterialSpec::Metal(albedo, fuzz)
+                        },
+                        "dielectric" => {
+                            let (ior, position) = properties.required_number("ior")?;
+                            if ior <= 0.0 {
+                                return Err(parser.error(position, "'ior' must be positive".to_string()));
+                            }
+                            MaterialSpec::Dielectric(ior)
+                        },
+                        "light" => {
+                            let (color, intensity) = properties.emission()?;
+                            let two_sided = properties.boolean("two_sided")?.unwrap_or(true);
+                            MaterialSpec::Light(color.to_point3(), intensity, two_sided)
+                        },
+                        _ => return Err(parser.error(position, format!("unknown material kind '{}'", kind)))
+                    };
+                    materials.push((name, spec));
+                },
+                ("sphere", None) => {
+                    let center = properties.required_vector("center")?.0;
+                    let (radius, position) = properties.required_number("radius")?;
+                    if radius <= 0.0 {
+                        return Err(parser.error(position, "'radius' must be positive".to_string()));
+                    }
+                    let material = properties.material(&materials)?.build().map_err(invalid)?;
+                    added.push(Box::new(Sphere::construct(center, radius, material).map_err(invalid)?));
+                },
+                ("plane", None) => {
+                    let point = properties.required_vector("point")?.0;
+                    let (normal, position) = properties.required_vector("normal")?;
+                    if normal.norm() == 0.0 {
+                        return Err(parser.error(position, "'normal' cannot be zero".to_string()));
+                    }
+                    let material = properties.material(&materials)?.build().map_err(invalid)?;
+                    added.push(Box::new(Plane::construct(point, normal, material).map_err(invalid)?));
+                },
+                ("triangle", None) => {
+                    let a = properties.required_vector("a")?.0;
+                    let b = properties.required_vector("b")?.0;
+                    let c = properties.required_vector("c")?.0;
+                    if (b - a).cross(c - a).norm() == 0.0 {
+                        return Err(parser.error(position, "triangle vertices are collinear".to_string()));
+                    }
+                    let material = properties.material(&materials)?.build().map_err(invalid)?;
+                    added.push(Box::new(Triangle::construct(a, b, c, material).map_err(invalid)?));
+                },
+                ("quad", None) => {
+                    let corner = properties.required_vector("corner")?.0;
+                    let u = properties.required_vector("u")?.0;
+                    let v = properties.required_vector("v")?.0;
+                    if u.cross(v).norm() == 0.0 {
+                        return Err(parser.error(position, "quad edges are parallel".to_string()));
+                    }
+                    let sampling = properties.sampling()?;
+                    let material = properties.material(&materials)?.build().map_err(invalid)?;
+                    let mut quad = Quad::construct(corner, u, v, material).map_err(invalid)?;
+                    quad.sampling = sampling;
+                    added.push(Box::new(quad));
+                },
+                ("disk", None) => {
+                    let center = properties.required_vector("center")?.0;
+                    let (normal, position) = properties.required_vector("normal")?;
+                    if normal.norm() == 0.0 {
+                        return Err(parser.error(position, "'normal' cannot be zero".to_string()));
+                    }
+                    let (radius, position) = properties.required_number("radius")?;
+                    if radius <= 0.0 {
+                        return Err(parser.error(position, "'radius' must be positive".to_string()));
+                    }
+                    let sampling = properties.sampling()?;
+                    let material = properties.material(&materials)?.build().map_err(invalid)?;
+                    let mut disk = Disk::construct(center, normal, radius, material).map_err(invalid)?;
+                    disk.sampling = sampling;
+                    added.push(Box::new(disk));
+                },
+                ("mesh", None) => {
+                    let (file, position) = properties.text("file")?.ok_or_else(|| properties.missing("file"))?;
+                    let material = match properties.block.properties.iter().any(|p| p.key == "material") {
+                        true => Some(properties.material(&materials)?),
+                        false => None
+                    };
+                    let meshes = obj::load(&directory.join(&file))
+                        .map_err(|error| parser.error(position, format!("cannot load mesh: {}", error)))?;
+                    for mut mesh in meshes {
+                        if let Some(material) = material {
+                            mesh.material = material.build().map_err(invalid)?;
+                        }
+                        added.push(Box::new(mesh));
+                    }
+                },
+                ("point_light", None) => {
+                    let position = properties.required_vector("position")?.0;
+                    let (color, intensity) = properties.emission()?;
+                    scene.world.add_light(Box::new(PointLight::construct(position, color, intensity).map_err(invalid)?));
+                },
+                ("spot_light", None) => {
+                    let position = properties.required_vector("position")?.0;
+                    let direction = match (properties.vector("direction")?, properties.vector("look_at")?) {
+                        (Some(_), Some((_, at))) => return Err(parser.error(at, "give either 'direction' or 'look_at', not both".to_string())),
+                        (Some((direction, _)), None) => direction,
+                        (None, Some((look_at, _))) => look_at - position,
+                        (None, None) => return Err(properties.missing("direction"))
+                    };
+                    let angle = properties.required_number("angle")?.0;
+                    let falloff = properties.number("falloff")?.map_or(0.0, |(n, _)| n);
+                    let (color, intensity) = properties.emission()?;
+                    scene.world.add_light(Box::new(SpotLight::construct(position, direction, angle, falloff, color, intensity).map_err(invalid)?));
+                },
+                ("sun", None) => {
+                    let direction = properties.required_vector("direction")?.0;
+                    let diameter = properties.number("angular_diameter")?.map_or(0.0, |(n, _)| n);
+                    let (color, intensity) = properties.emission()?;
+                    scene.world.add_light(Box::new(DirectionalLight::construct(direction, diameter, color, intensity).map_err(invalid)?));
+                },
+                (kind, None) => return Err(parser.error(position, format!("unknown block '{}'", kind)))
+            }
+            properties.finish()?;
+            match motion {
+                Some(MotionSpec {translation, spin, axis, pivot}) => {
+                    // by default everything the block made spins about its common center
+                    let pivot = pivot.unwrap_or_else(|| added.iter()
+                        .filter_map(|object| object.bounding_box())
+                        .reduce(|a, b| a.union(b))
+                        .map_or(ORIGIN, |bounds| bounds.centroid()));
+                    for object in added {
+                        scene.world.add(Box::new(Moving::construct(object, translation, pivot, axis, spin.to_radians()).map_err(invalid)?));
+                    }
+                },
+                None => added.into_iter().for_each(|object| scene.world.add(object))
+            }
+        }
+        Ok(scene)
+    }
+    /// The scene rendered when no scene file is given.
+    pub fn demo() -> Result<Scene, RaytraceError> {
+        Ok(Scene::construct(World::construct(vec![
+            // ground
+            Box::new(Plane::construct(Point3::construct(0.0, 0.0, -0.5), UNIT_Z, Box::new(Diffuse::construct(0.8, 0.8, 0.0)?))?),
+            // middle
+            Box::new(Sphere::construct(Point3::construct(0.0, 1.2, 0.0), 0.5, Box::new(Diffuse::construct(0.1, 0.2, 0.5)?))?),
+            // left, a hollow glass ball
+            Box::new(Sphere::construct(Point3::construct(-1.0, 1.0, 0.0), 0.5, Box::new(Dielectric::construct(1.5)?))?),
+            Box::new(Sphere::construct(Point3::construct(-1.0, 1.0, 0.0), 0.4, Box::new(Dielectric::construct(1.0/1.5)?))?),
+            // right
+            Box::new(Sphere::construct(Point3::construct(1.0, 1.0, 0.0), 0.5, Box::new(Metallic::construct(0.8, 0.6, 0.2, 0.1)?))?)
+        ])))
+    }
+    pub fn camera(&self) -> Result<Camera<'_>, RaytraceError> {
+        let (width, height) = self.settings.resolution();
+        let mut camera = Camera::construct(&self.world, width, height, &self.view)?;
+        camera.pixel_samples = self.settings.pixel_samples.unwrap_or(RenderSettings::DEFAULT_PIXEL_SAMPLES);
+        camera.scatter_depth = self.settings.scatter_depth.unwrap_or(RenderSettings::DEFAULT_SCATTER_DEPTH);
+        camera.threads = self.settings.threads.unwrap_or(0);
+        if let Some(seed) = self.settings.seed {
+            camera.seed = seed;
+        }
+        Ok(camera)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{Ray, INFINITY};
+
+    // the line, column and message of the error parsing source
+    fn error(source: &str) -> (usize, usize, String) {
+        match Scene::parse(source, Path::new("test.scene")) {
+            Err(SceneError::Parse {line, column, message, ..}) => (line, column, message),
+            Err(error) => panic!("expected a parse error, got {}", error),
+            Ok(_) => panic!("expected a parse error")
+        }
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        assert_eq!(error("settings { width = 4 }\n  teapot { }"), (2, 3, "unknown block 'teapot'".to_string()));
+        assert_eq!(error("material m = diffuse { albedo = (1, 1, 1) }\nsphere { center = (0, 0, 0)  radius = 1  material = m  colour = (1, 0, 0) }"), (2, 56, "unknown property 'colour' for sphere".to_string()));
+        assert_eq!(error("\nmesh { file = \"teapot.obj }"), (2, 15, "unterminated string".to_string()));
+        assert_eq!(error("settings {\n    aspect = 1.6.9\n}"), (2, 14, "invalid number '1.6.9'".to_string()));
+        assert_eq!(error("material m = diffuse { albedo = (1, 1, 1) }\n\tsphere { center = (0, 0, 0)  material = m }"), (2, 2, "sphere needs 'radius'".to_string()));
+        assert_eq!(error("camera { look_from = (0, 0) }"), (1, 27, "expected ',', found ')'".to_string()));
+        assert_eq!(error("settings { width = 4"), (1, 21, "expected a property name or '}', found the end of the file".to_string()));
+    }
+
+    #[test]
+    fn integers_out_of_range_are_too_large() {
+        for width in ["3e9", "1e19", "1e300"] {
+            assert_eq!(error(&format!("settings {{ width = {} }}", width)), (1, 12, "'width' is too large".to_string()));
+        }
+        assert_eq!(error("settings { width = 2.5 }"), (1, 12, "'width' should be a whole number of at least 1".to_string()));
+        assert_eq!(error("settings { seed = -1 }"), (1, 12, "'seed' should be a whole number of at least 0".to_string()));
+    }
+
+    #[test]
+    fn demo_scene_file_matches_the_demo() {
+        let scene = Scene::parse(include_str!("../scenes/demo.scene"), Path::new("scenes/demo.scene")).unwrap();
+        let settings = &scene.settings;
+        assert_eq!((settings.width, settings.height, settings.pixel_samples, settings.scatter_depth), (Some(512), None, Some(3), Some(10)));
+        assert_eq!(settings.aspect, Some(16.0 / 9.0));
+        assert_eq!(scene.view.projection, View::DEFAULT.projection);
+        assert_eq!((scene.view.look_at.x, scene.view.look_at.y, scene.view.look_at.z), (0.0, 1.0, 0.0));
+        assert_eq!(scene.view.vfov, 90.0);
+
+        let demo = Scene::demo().unwrap();
+        assert_eq!(scene.world.objects().len(), demo.world.objects().len());
+        for direction in [UNIT_Y, Point3 {x: -1.0, y: 1.0, z: 0.2}, Point3 {x: 1.0, y: 1.0, z: -0.1}, Point3 {x: 0.3, y: 1.0, z: -1.0}] {
+            let ray = Ray {A: direction, B: ORIGIN, time: 0.0};
+            let (hit, expected) = (scene.world.hit(&ray, (0.0, INFINITY)).unwrap(), demo.world.hit(&ray, (0.0, INFINITY)).unwrap());
+            assert_eq!(hit.t, expected.t);
+        }
+    }
+}
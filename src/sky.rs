@@ -0,0 +1,239 @@
+use std::f64::consts::PI;
+use crate::utils::{Point3, Color3, BLACK, sample_cone};
+use crate::background::Background;
+use crate::light::DirectionalLight;
+use crate::error::RaytraceError;
+
+// Preetham, Shirley and Smits, "A Practical Analytic Model for Daylight" (1999): the sky's
+// luminance and chromaticity relative to the zenith follow the Perez distribution, with
+// coefficients linear in turbidity. Luminance is in kcd/m^2.
+
+// the Perez distribution, at angle theta from the zenith and gamma from the sun
+#[derive(Copy, Clone, Debug)]
+struct Perez {
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+    e: f64
+}
+
+impl Perez {
+    fn value(&self, cos_theta: f64, gamma: f64) -> f64 {
+        let cos_gamma = gamma.cos();
+        (1.0 + self.a * (self.b / cos_theta.max(0.01)).exp())
+            * (1.0 + self.c * (self.d * gamma).exp() + self.e * cos_gamma*cos_gamma)
+    }
+}
+
+// relative optical air mass towards a zenith angle, by Kasten's formula
+fn air_mass(theta: f64) -> f64 {
+    1.0 / (theta.cos() + 0.15 * (93.885 - theta.to_degrees()).max(0.0001).powf(-1.253))
+}
+
+fn xyy_to_rgb(x: f64, y: f64, luminance: f64) -> Color3 {
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+    // XYZ to linear sRGB
+    Color3 {
+        r: (3.2406*big_x - 1.5372*luminance - 0.4986*big_z).max(0.0),
+        g: (-0.9689*big_x + 1.8758*luminance + 0.0415*big_z).max(0.0),
+        b: (0.0557*big_x - 0.2040*luminance + 1.0570*big_z).max(0.0)
+    }
+}
+
+/// Daylight sky by the Preetham model, over +Z, for a sun at an elevation above the horizon and
+/// an azimuth from +Y towards +X (in degrees), in air of a turbidity from 1.7 (clear) to 10
+/// (hazy). Below the horizon is ground of the given albedo, lit by the sky and the sun. The sun
+/// is a disk in the sky, which light sampling aims at; with `sun` off, [`Sky::sun`] can light
+/// the scene instead, as a directional light that is not seen.
+pub struct Sky {
+    sun_direction: Point3,  // unit, towards the sun
+    turbidity: f64,
+    ground: Color3,         // radiance of the ground
+    zenith: (f64, f64, f64),    // chromaticity x, y and luminance Y straight up
+    perez: (Perez, Perez, Perez),
+    pub intensity: f64,  // scales the sky and the sun; the default puts a white surface in full sun near 1
+    pub sun: bool        // whether the sun's disk is in the sky
+}
+
+impl Sky {
+    pub const DEFAULT_INTENSITY: f64 = 0.04;
+    pub const SUN_ANGULAR_DIAMETER: f64 = 0.53;
+
+    pub fn construct(sun_elevation: f64, sun_azimuth: f64, turbidity: f64, ground_albedo: Color3) -> Result<Sky, RaytraceError> {
+        if !(0.0..=90.0).contains(&sun_elevation) {
+            return Err(RaytraceError::InvalidSunElevation(sun_elevation));
+        }
+        if !(1.7..=10.0).contains(&turbidity) {
+            return Err(RaytraceError::InvalidTurbidity(turbidity));
+        }
+        let Color3 {r, g, b} = ground_albedo;
+        if [r, g, b].iter().any(|c| !(0.0..=1.0).contains(c)) {
+            return Err(RaytraceError::InvalidAlbedo {r, g, b});
+        }
+        let (elevation, azimuth) = (sun_elevation.to_radians(), sun_azimuth.to_radians());
+        let sun_direction = Point3 {x: elevation.cos()*azimuth.sin(), y: elevation.cos()*azimuth.cos(), z: elevation.sin()};
+
+        let t = turbidity;
+        let theta = PI/2.0 - elevation;  // of the sun, from the zenith
+        let chi = (4.0/9.0 - t/120.0) * (PI - 2.0*theta);
+        let zenith_luminance = (4.0453*t - 4.9710) * chi.tan() - 0.2155*t + 2.4192;
+        let (theta2, theta3) = (theta*theta, theta*theta*theta);
+        let zenith_x = t*t * (0.00166*theta3 - 0.00375*theta2 + 0.00209*theta)
+            + t * (-0.02903*theta3 + 0.06377*theta2 - 0.03202*theta + 0.00394)
+            + (0.11693*theta3 - 0.21196*theta2 + 0.06052*theta + 0.25886);
+        let zenith_y = t*t * (0.00275*theta3 - 0.00610*theta2 + 0.00317*theta)
+            + t * (-0.04214*theta3 + 0.08970*theta2 - 0.04153*theta + 0.00516)
+            + (0.15346*theta3 - 0.26756*theta2 + 0.06670*theta + 0.26688);
+        let perez = (
+            Perez {a: 0.1787*t - 1.4630, b: -0.3554*t + 0.4275, c: -0.0227*t + 5.3251, d: 0.1206*t - 2.5771, e: -0.0670*t + 0.3703},
+            Perez {a: -0.0193*t - 0.2592, b: -0.0665*t + 0.0008, c: -0.0004*t + 0.2125, d: -0.0641*t - 0.8989, e: -0.0033*t + 0.0452},
+            Perez {a: -0.0167*t - 0.2608, b: -0.0950*t + 0.0092, c: -0.0079*t + 0.2102, d: -0.0441*t - 1.6537, e: -0.0109*t + 0.0529}
+        );
+        let mut sky = Sky {
+            sun_direction, turbidity, ground: BLACK,
+            zenith: (zenith_x, zenith_y, zenith_luminance), perez, intensity: 1.0, sun: true
+        };
+
+        // the ground is lambertian, under the irradiance of the sky and the sun on it
+        let (steps_theta, steps_phi) = (32, 64);
+        let (d_theta, d_phi) = (PI/2.0 / steps_theta as f64, 2.0*PI / steps_phi as f64);
+        let mut irradiance = sky.sun_irradiance() * sun_direction.z;
+        for i in 0..steps_theta {
+            let theta = (i as f64 + 0.5) * d_theta;
+            for j in 0..steps_phi {
+                let phi = (j as f64 + 0.5) * d_phi;
+                let direction = Point3 {x: theta.sin()*phi.cos(), y: theta.sin()*phi.sin(), z: theta.cos()};
+                irradiance += sky.sky_radiance(direction) * (theta.cos() * theta.sin() * d_theta * d_phi);
+            }
+        }
+        sky.ground = ground_albedo * irradiance * (1.0 / PI);
+        sky.intensity = Self::DEFAULT_INTENSITY;
+        Ok(sky)
+    }
+    pub fn sun_direction(&self) -> Point3 {
+        self.sun_direction
+    }
+    // unscaled radiance of the sky, above the horizon
+    fn sky_radiance(&self, direction: Point3) -> Color3 {
+        let cos_theta = direction.z.max(0.0);
+        let gamma = direction.dot(self.sun_direction).clamp(-1.0, 1.0).acos();
+        let sun_theta = PI/2.0 - self.sun_direction.z.asin();
+        let (x_zenith, y_zenith, luminance_zenith) = self.zenith;
+        let (perez_luminance, perez_x, perez_y) = self.perez;
+        let relative = |perez: Perez| perez.value(cos_theta, gamma) / perez.value(1.0, sun_theta);
+        xyy_to_rgb(x_zenith * relative(perez_x), y_zenith * relative(perez_y), luminance_zenith * relative(perez_luminance))
+    }
+    // unscaled irradiance of the sun, on a surface facing it: the light outside the atmosphere,
+    // about 128 klx, through Rayleigh scattering and aerosols (Angstrom's formula) at the
+    // wavelengths of red, green and blue
+    fn sun_irradiance(&self) -> Color3 {
+        let mass = air_mass(PI/2.0 - self.sun_direction.z.asin());
+        let beta = 0.04608*self.turbidity - 0.04586;
+        let transmittance = |wavelength: f64| {  // micrometers
+            (-0.008735 * wavelength.powf(-4.08) * mass).exp() * (-beta * wavelength.powf(-1.3) * mass).exp()
+        };
+        Color3 {r: transmittance(0.65), g: transmittance(0.55), b: transmittance(0.45)} * 128.0
+    }
+    // cosine of the angle from the middle of the sun to its edge
+    fn cos_sun_radius() -> f64 {
+        (Self::SUN_ANGULAR_DIAMETER / 2.0).to_radians().cos()
+    }
+    // unscaled radiance of the sun's disk, which gives its irradiance over its solid angle
+    fn sun_radiance(&self) -> Color3 {
+        self.sun_irradiance() * (1.0 / (2.0*PI * (1.0 - Self::cos_sun_radius())))
+    }
+    /// The sun of this sky as a directional light, to add to the world's lights in place of the
+    /// sun's disk (with `sun` off); an error only for an invalid intensity.
+    pub fn sun(&self) -> Result<DirectionalLight, RaytraceError> {
+        let irradiance = self.sun_irradiance();
+        let brightest = irradiance.r.max(irradiance.g).max(irradiance.b);
+        DirectionalLight::construct(-self.sun_direction, Self::SUN_ANGULAR_DIAMETER, irradiance / brightest, brightest * self.intensity)
+    }
+}
+
+impl Background for Sky {
+    fn radiance(&self, direction: Point3) -> Color3 {
+        let radiance = if direction.z < 0.0 {
+            self.ground
+        } else if self.sun && direction.dot(self.sun_direction) >= Self::cos_sun_radius() {
+            self.sky_radiance(direction) + self.sun_radiance()
+        } else {
+            self.sky_radiance(direction)
+        };
+        radiance * self.intensity
+    }
+    // only the sun is sampled; the rest of the sky is smooth enough to be found by scattering
+    fn is_emitter(&self) -> bool {
+        self.sun
+    }
+    fn sample(&self, u: (f64, f64)) -> Option<Point3> {
+        self.sun.then(|| sample_cone(self.sun_direction, Self::cos_sun_radius(), u))
+    }
+    fn pdf(&self, direction: Point3) -> f64 {
+        if self.sun && direction.dot(self.sun_direction) >= Self::cos_sun_radius() {
+            1.0 / (2.0*PI * (1.0 - Self::cos_sun_radius()))
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+    use crate::light::Light;
+    use crate::random::Pcg32;
+    use crate::utils::{WHITE, ORIGIN, UNIT_Z};
+
+    #[test]
+    fn sun_disk_lights_like_the_directional_sun() {
+        let mut rng = Pcg32::construct(7, 0);
+        for elevation in [10.0, 35.0, 80.0] {
+            let sky = Sky::construct(elevation, 120.0, 3.0, WHITE * 0.3).unwrap();
+            let mut clear = Sky::construct(elevation, 120.0, 3.0, WHITE * 0.3).unwrap();
+            clear.sun = false;
+            let light = sky.sun().unwrap();
+            // irradiance on the ground from the disk alone, without the sky around it
+            let (mut with_sun, mut without, mut directional) = (BLACK, BLACK, BLACK);
+            for _ in 0..10000 {
+                let u = (rng.random(), rng.random());
+                let direction = sky.sample(u).unwrap();
+                let weight = direction.z / sky.pdf(direction);
+                with_sun += sky.radiance(direction) * weight;
+                without += clear.radiance(direction) * weight;
+                let sample = light.sample(ORIGIN, u).unwrap();
+                directional += sample.radiance * sample.direction.z;
+            }
+            let disk = [with_sun.r - without.r, with_sun.g - without.g, with_sun.b - without.b];
+            for (a, b) in disk.into_iter().zip([directional.r, directional.g, directional.b]) {
+                assert!((a - b).abs() < 1e-3 * b, "{} vs {} at {} degrees", a, b, elevation);
+            }
+        }
+    }
+
+    #[test]
+    fn sun_pdf_is_zero_off_the_disk() {
+        let mut sky = Sky::construct(35.0, 120.0, 3.0, WHITE * 0.3).unwrap();
+        let sun = sky.sun_direction();
+        let pdf = sky.pdf(sun);
+        // uniform over the disk, so it integrates to 1 over the disk's solid angle
+        assert!((pdf * 2.0*PI * (1.0 - Sky::cos_sun_radius()) - 1.0).abs() < 1e-12);
+        let mut rng = Pcg32::construct(8, 0);
+        for _ in 0..1000 {
+            let direction = sky.sample((rng.random(), rng.random())).unwrap();
+            assert!(direction.dot(sun) >= Sky::cos_sun_radius() - 1e-12);
+            assert_eq!(sky.pdf(direction), pdf);
+        }
+        // just past the edge of the disk, and far from it
+        let edge = (Sky::SUN_ANGULAR_DIAMETER / 2.0 * 1.01).to_radians();
+        let axis = sun.cross(UNIT_Z).unit_vector();
+        assert_eq!(sky.pdf(sun.rotate(axis, edge)), 0.0);
+        assert_eq!(sky.pdf(-sun), 0.0);
+        sky.sun = false;
+        assert_eq!(sky.pdf(sun), 0.0);
+        assert!(sky.sample((0.5, 0.5)).is_none());
+    }
+}
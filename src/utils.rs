@@ -1,5 +1,8 @@
 use std::ops;
+use rand::RngCore;
+use crate::error::RaytraceError;
 
+/// Point or vector in 3D space.
 #[derive(Copy, Clone, Debug)]
 pub struct Point3 {
     pub x: f64,
@@ -40,6 +43,23 @@ impl Point3 {
             z: self.x*rhs.y - self.y*rhs.x
         }
     }
+    /// The vector turned counterclockwise about a unit axis, by the right hand rule (Rodrigues' formula).
+    pub fn rotate(self, axis: Point3, angle: f64) -> Point3 {
+        let (sin, cos) = angle.sin_cos();
+        self*cos + axis.cross(self)*sin + axis*axis.dot(self)*(1.0 - cos)
+    }
+}
+
+/// A direction uniformly distributed over the directions within the cone about a unit axis whose half angle
+/// has cosine `cos_max`, with `u` uniform in [0, 1)^2.
+pub fn sample_cone(axis: Point3, cos_max: f64, u: (f64, f64)) -> Point3 {
+    let cos = 1.0 - u.0 * (1.0 - cos_max);
+    let sin = (1.0 - cos*cos).max(0.0).sqrt();
+    let phi = 2.0 * std::f64::consts::PI * u.1;
+    let helper = if axis.x.abs() > 0.9 { UNIT_Y } else { UNIT_X };
+    let tangent = axis.cross(helper).unit_vector();
+    let bitangent = axis.cross(tangent);
+    tangent*(sin*phi.cos()) + bitangent*(sin*phi.sin()) + axis*cos
 }
 
 impl ops::Neg for Point3 {
@@ -149,51 +169,108 @@ impl ops::DivAssign<f64> for Point3 {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+/// Linear radiance, unbounded; only quantized to 8 bits when an image is written
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Color3 {
-    pub r: i32,
-    pub g: i32,
-    pub b: i32
+    pub r: f64,
+    pub g: f64,
+    pub b: f64
 }
 
+pub const BLACK: Color3 = Color3 {r: 0.0, g: 0.0, b: 0.0};
+pub const WHITE: Color3 = Color3 {r: 1.0, g: 1.0, b: 1.0};
+
 impl Color3 {
     const GAMMA_CORRECT: bool = true;
-    pub fn construct(r_: f64, g_: f64, b_: f64) -> Color3 {
-        for c in vec![r_, g_, b_] {
-            if c < 0.0 || c > 1.0 {
-                panic!("Invalid colouring! r={} g={} b={}", r_, g_, b_);
-            }
+    pub fn construct(r: f64, g: f64, b: f64) -> Result<Color3, RaytraceError> {
+        if [r, g, b].iter().any(|c| !c.is_finite() || *c < 0.0) {
+            return Err(RaytraceError::InvalidColor {r, g, b});
         }
-        Color3 {r: (255.99*r_) as i32, g: (255.99*g_) as i32, b: (255.99*b_) as i32}
+        Ok(Color3 {r, g, b})
     }
     pub fn from_point3(point: Point3) -> Color3 {
-        Self::construct(point.x, point.y, point.z)
+        Color3 {r: point.x, g: point.y, b: point.z}
+    }
+    pub fn to_point3(self) -> Point3 {
+        Point3 {x: self.r, y: self.g, z: self.b}
+    }
+    /// The color gamma corrected and clamped to [0, 1], then quantized.
+    pub fn to_rgb8(self) -> [u8; 3] {
+        let quantize = |c: f64| {
+            let c = if c.is_nan() { 0.0 } else { c.clamp(0.0, 1.0) };
+            let c = if Self::GAMMA_CORRECT { c.powf(0.5) } else { c };
+            (255.99 * c) as u8
+        };
+        [quantize(self.r), quantize(self.g), quantize(self.b)]
     }
     pub fn print_out(&self) {
-        let mut gamma_corrected = self.to_point3();
-        if Self::GAMMA_CORRECT {
-            gamma_corrected.x = gamma_corrected.x.powf(0.5);
-            gamma_corrected.y = gamma_corrected.y.powf(0.5);
-            gamma_corrected.z = gamma_corrected.z.powf(0.5);
-        }
-        let c = Color3::from_point3(gamma_corrected);
-        println!("{} {} {}", c.r, c.g, c.b);
+        let [r, g, b] = self.to_rgb8();
+        println!("{} {} {}", r, g, b);
     }
-    pub fn to_point3(&self) -> Point3 {
-        Point3 {x: self.r as f64/255.99, y: self.g as f64/255.99, z: self.b as f64/255.99}
+}
+
+impl ops::Add<Color3> for Color3 {
+    type Output = Color3;
+    fn add(self, rhs: Color3) -> Color3 {
+        Color3 {r: self.r + rhs.r, g: self.g + rhs.g, b: self.b + rhs.b}
+    }
+}
+
+impl ops::AddAssign<Color3> for Color3 {
+    fn add_assign(&mut self, rhs: Color3) {
+        self.r += rhs.r;
+        self.g += rhs.g;
+        self.b += rhs.b;
+    }
+}
+
+impl ops::Mul<Color3> for Color3 {
+    type Output = Color3;
+    fn mul(self, rhs: Color3) -> Color3 {
+        Color3 {r: self.r * rhs.r, g: self.g * rhs.g, b: self.b * rhs.b}
     }
 }
 
+impl ops::Mul<f64> for Color3 {
+    type Output = Color3;
+    fn mul(self, rhs: f64) -> Color3 {
+        Color3 {r: self.r * rhs, g: self.g * rhs, b: self.b * rhs}
+    }
+}
+
+impl ops::Div<f64> for Color3 {
+    type Output = Color3;
+    fn div(self, rhs_: f64) -> Color3 {
+        let rhs = 1.0/rhs_;
+        Color3 {r: self.r * rhs, g: self.g * rhs, b: self.b * rhs}
+    }
+}
+
+impl ops::DivAssign<f64> for Color3 {
+    fn div_assign(&mut self, rhs: f64) {
+        self.r /= rhs;
+        self.g /= rhs;
+        self.b /= rhs;
+    }
+}
+
+/// Half line from `B` in the unit direction `A`, at an instant within the camera's shutter interval.
+#[allow(non_snake_case)]
 #[derive(Copy, Debug, Clone)]
 pub struct Ray {
     pub A: Point3,
-    pub B: Point3
+    pub B: Point3,
     // Ray(t: f64) = A*t + B
+    pub time: f64  // where moving objects are is a function of it
 }
 
 impl Ray {
+    #[allow(non_snake_case)]
     pub fn construct(A: Point3, B: Point3) -> Ray {
-        Ray {A: A.unit_vector(), B}
+        Ray {A: A.unit_vector(), B, time: 0.0}
+    }
+    pub fn with_time(self, time: f64) -> Ray {
+        Ray {time, ..self}
     }
     pub fn produce(&self, t: f64) -> Point3 {
         self.A * t + self.B
@@ -206,19 +283,36 @@ pub enum Face { // face to camera
     BackFace
 }
 
-pub trait Material {
-    fn scatter(&self, ray: &Ray, hit: &RayHit) -> Option<Ray>;
+/// How light scatters off a surface, and what light it gives off itself.
+pub trait Material: Send + Sync {
+    /// The ray scattered off the surface; None absorbs the ray.
+    fn scatter(&self, ray: &Ray, hit: &RayHit, rng: &mut dyn RngCore) -> Option<Ray>;
+    /// The light carried by a scattered ray, as it leaves the surface.
     fn attenuate(&self, color: Color3) -> Color3;
+    /// Radiance given off towards where the ray came from.
+    fn emitted(&self, _hit: &RayHit) -> Color3 {
+        BLACK
+    }
+    /// Whether `emitted` is ever anything but black.
+    fn is_emitter(&self) -> bool {
+        false
+    }
+    /// For light sampling: the scattering function times the cosine towards a unit direction, and
+    /// the density `scatter` picks that direction with; None for mirror-like (specular) materials,
+    /// which only scatter into directions no light sample will ever hit.
+    fn bsdf(&self, _ray: &Ray, _hit: &RayHit, _direction: Point3) -> Option<(Color3, f64)> {
+        None
+    }
 }
 
+/// Where a ray hits an object; a miss is `None` wherever hits are returned.
 #[derive(Clone)]
-pub enum RayHit<'a> {
-    Hit {
-        t: f64,
-        point: Point3,
-        normal: Point3,
-        face: Face,
-        material: &'a Box<dyn Material>
-    },
-    NoHit
+pub struct RayHit<'a> {
+    pub t: f64,
+    pub point: Point3,
+    pub normal: Point3,  // unit, on the side the ray came from
+    pub face: Face,
+    pub barycentric: Option<(f64, f64)>,  // (u, v) weights of the second and third vertex, for triangles
+    pub uv: Option<(f64, f64)>,  // texture coordinates, where the object has them
+    pub material: &'a dyn Material
 }
\ No newline at end of file